@@ -0,0 +1,25 @@
+// find-folly/src/bindgen_builder.rs
+//
+//! Generating Rust bindings for Folly's headers with `bindgen`. This module is only available with
+//! the `bindgen` feature.
+
+use crate::Folly;
+
+impl Folly {
+    /// Returns a `bindgen::Builder` that parses headers with [`Folly::clang_args()`], so that
+    /// `bindgen` sees the same flags as the C++ compiler.
+    ///
+    /// Headers, allowlists, and the like are left to the caller:
+    ///
+    /// ```ignore
+    /// let bindings = folly
+    ///     .bindgen_builder()
+    ///     .header("src/wrapper.h")
+    ///     .allowlist_function("folly_shim_.*")
+    ///     .generate()
+    ///     .unwrap();
+    /// ```
+    pub fn bindgen_builder(&self) -> bindgen::Builder {
+        bindgen::Builder::default().clang_args(self.clang_args())
+    }
+}
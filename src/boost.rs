@@ -0,0 +1,427 @@
+// find-folly/src/boost.rs
+//
+//! Locating the Boost libraries that Folly links against.
+//!
+//! Folly's `.pc` file doesn't mention Boost at all, and Boost's own naming is anything but uniform:
+//! depending on how it was built, `boost_context` may be installed as `libboost_context.a`,
+//! `libboost_context-mt.a`, or `libboost_context-gcc11-mt-x64-1_79.a`, and it may live in Folly's
+//! prefix, in a separate Boost prefix, or in a system multiarch directory. This module searches all
+//! of those and picks the best match for the requested link mode.
+
+use crate::link::{self, LinkKind};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The Boost components Folly is built against.
+pub(crate) const COMPONENTS: &[&str] = &[
+    "context",
+    "filesystem",
+    "program_options",
+    "regex",
+    "system",
+    "thread",
+];
+
+/// The environment variables that affect where Boost is found.
+pub(crate) const ENV_VARS: &[&str] = &[
+    "BOOST_ROOT",
+    "BOOST_LIBRARYDIR",
+    "BOOST_INCLUDEDIR",
+    "Boost_DIR",
+];
+
+/// A Boost library that the probe chose to link against.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BoostLibrary {
+    /// The Boost component, such as `context`.
+    pub component: String,
+    /// The library file that was chosen.
+    pub path: PathBuf,
+    /// The name the library is linked by, such as `boost_context-mt`.
+    pub name: String,
+    /// Whether the file is a static or a shared library.
+    pub kind: LinkKind,
+}
+
+/// The result of resolving one component.
+pub(crate) struct Resolved {
+    pub(crate) library: BoostLibrary,
+    /// The directory to add to the linker's search path, or `None` if the linker already searches
+    /// it.
+    pub(crate) search_dir: Option<PathBuf>,
+}
+
+/// Searches for Boost libraries.
+pub(crate) struct BoostResolver {
+    search_dirs: Vec<PathBuf>,
+    // The directories the linker searches by default.
+    system_dirs: Vec<PathBuf>,
+    statik: bool,
+}
+
+// A library file that matches a component, and how well it matches.
+struct Candidate {
+    path: PathBuf,
+    name: String,
+    kind: LinkKind,
+    debug: bool,
+    tagged: bool,
+}
+
+impl BoostResolver {
+    /// Creates a resolver that searches `first_dirs`, then the directories named by `BOOST_ROOT`,
+    /// `BOOST_LIBRARYDIR`, and `Boost_DIR`, then prefixes in `CMAKE_PREFIX_PATH` that contain Boost,
+    /// then the system library directories, under `sysroot` if there is one.
+    pub(crate) fn new(first_dirs: Vec<PathBuf>, statik: bool, sysroot: Option<&Path>) -> Self {
+        let mut search_dirs = first_dirs;
+        if let Some(dir) = env::var_os("BOOST_LIBRARYDIR") {
+            search_dirs.push(PathBuf::from(dir));
+        }
+        if let Some(root) = env::var_os("BOOST_ROOT") {
+            let root = PathBuf::from(root);
+            search_dirs.push(root.join("lib"));
+            search_dirs.push(root.join("lib64"));
+            // A Boost source tree that was built in place.
+            search_dirs.push(root.join("stage").join("lib"));
+        }
+        // `BoostConfig.cmake` lives in `<libdir>/cmake/Boost-<version>`.
+        if let Some(dir) = env::var_os("Boost_DIR") {
+            let dir = PathBuf::from(dir);
+            search_dirs.extend(dir.parent().and_then(Path::parent).map(Path::to_owned));
+        }
+        let prefixes = env::var_os("CMAKE_PREFIX_PATH")
+            .map(|prefix_path| env::split_paths(&prefix_path).collect::<Vec<_>>())
+            .unwrap_or_default();
+        for prefix in &prefixes {
+            for lib_dir in ["lib", "lib64"] {
+                let lib_dir = prefix.join(lib_dir);
+                if has_boost_config(&lib_dir) || prefix.join("include").join("boost").is_dir() {
+                    search_dirs.push(lib_dir);
+                }
+            }
+        }
+
+        if cfg!(target_os = "macos") {
+            search_dirs.push(PathBuf::from("/opt/homebrew/lib"));
+        }
+        let system_dirs = link::system_lib_dirs(sysroot);
+        search_dirs.extend(system_dirs.iter().cloned());
+        let mut unique_dirs: Vec<PathBuf> = vec![];
+        for dir in search_dirs {
+            if !unique_dirs.contains(&dir) {
+                unique_dirs.push(dir);
+            }
+        }
+        Self {
+            search_dirs: unique_dirs,
+            system_dirs,
+            statik,
+        }
+    }
+
+    /// The directories searched, in order.
+    pub(crate) fn search_dirs(&self) -> &[PathBuf] {
+        &self.search_dirs
+    }
+
+    /// Finds the best library file for `component`, such as `context`.
+    ///
+    /// A library of the requested kind (static or shared) is preferred over a directory that comes
+    /// earlier in the search order. After that, release builds are preferred to debug builds, and
+    /// plain names to tagged ones.
+    pub(crate) fn resolve(&self, component: &str) -> Option<Resolved> {
+        let wanted_kind = if self.statik {
+            LinkKind::Static
+        } else {
+            LinkKind::Dylib
+        };
+        let mut best: Option<((bool, usize, bool, bool), Candidate)> = None;
+        for (dir_index, dir) in self.search_dirs.iter().enumerate() {
+            let entries = match fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            let mut file_names: Vec<_> = entries
+                .flatten()
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .collect();
+            // `read_dir` order is arbitrary; sort so that the choice is deterministic.
+            file_names.sort();
+            for file_name in file_names {
+                let candidate = match parse_file_name(&file_name, component) {
+                    Some((name, kind, tags)) => Candidate {
+                        path: dir.join(&file_name),
+                        name,
+                        kind,
+                        debug: is_debug(&tags),
+                        tagged: !tags.is_empty(),
+                    },
+                    None => continue,
+                };
+                let rank = (
+                    candidate.kind != wanted_kind,
+                    dir_index,
+                    candidate.debug,
+                    candidate.tagged,
+                );
+                if best.as_ref().is_none_or(|(best_rank, _)| rank < *best_rank) {
+                    best = Some((rank, candidate));
+                }
+            }
+        }
+
+        let (_, candidate) = best?;
+        let dir = candidate.path.parent().map(Path::to_owned);
+        Some(Resolved {
+            search_dir: dir.filter(|dir| !self.system_dirs.contains(dir)),
+            library: BoostLibrary {
+                component: component.to_owned(),
+                path: candidate.path,
+                name: candidate.name,
+                kind: candidate.kind,
+            },
+        })
+    }
+}
+
+// If `file_name` is a linkable library for `component`, returns the name to link it by, whether
+// it's static or shared, and its tags (the `-`-separated parts after the library name).
+//
+// Boost's "versioned" and "tagged" layouts append tags such as the toolset (`gcc11`), threading
+// (`mt`), ABI (`sgd`), architecture (`x64`), and version (`1_79`) to the name. Versioned shared
+// libraries like `libboost_context.so.1.79.0` are skipped, since the linker can't find them with
+// `-l`.
+fn parse_file_name(file_name: &str, component: &str) -> Option<(String, LinkKind, Vec<String>)> {
+    let (name, kind) = if let Some(stem) = file_name.strip_suffix(".a") {
+        (stem.strip_prefix("lib")?, LinkKind::Static)
+    } else if let Some(stem) = file_name
+        .strip_suffix(".so")
+        .or_else(|| file_name.strip_suffix(".dylib"))
+    {
+        (stem.strip_prefix("lib")?, LinkKind::Dylib)
+    } else if let Some(stem) = file_name.strip_suffix(".lib") {
+        // On Windows, static libraries have a `lib` prefix and import libraries don't. Either way,
+        // the linker is given the whole stem.
+        let kind = if stem.starts_with("lib") {
+            LinkKind::Static
+        } else {
+            LinkKind::Dylib
+        };
+        let base = stem.strip_prefix("lib").unwrap_or(stem);
+        return matches_component(base, component).map(|tags| (stem.to_owned(), kind, tags));
+    } else {
+        return None;
+    };
+    matches_component(name, component).map(|tags| (name.to_owned(), kind, tags))
+}
+
+// If `name` is `boost_<component>`, optionally followed by tags, returns the tags.
+fn matches_component(name: &str, component: &str) -> Option<Vec<String>> {
+    let rest = name.strip_prefix("boost_")?.strip_prefix(component)?;
+    if rest.is_empty() {
+        return Some(vec![]);
+    }
+    let tags = rest.strip_prefix('-')?;
+    Some(tags.split('-').map(str::to_owned).collect())
+}
+
+// The ABI tag contains `d` for debug builds of Boost itself, as in `gd` or `sgd`.
+fn is_debug(tags: &[String]) -> bool {
+    tags.iter().any(|tag| {
+        tag.contains('d')
+            && tag
+                .chars()
+                .all(|c| matches!(c, 's' | 'g' | 'y' | 'd' | 'p' | 'n'))
+    })
+}
+
+/// Finds the directory containing Boost's headers: `BOOST_INCLUDEDIR`, or else the one that goes
+/// with `library`, a Boost library that was chosen. That's `<prefix>/include` for a library in
+/// `<prefix>/lib` or `<prefix>/lib/<multiarch>`, or the root of a source tree for one in
+/// `stage/lib`.
+pub(crate) fn include_dir(library: Option<&Path>) -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("BOOST_INCLUDEDIR") {
+        return Some(PathBuf::from(dir));
+    }
+    let lib_dir = library?.parent()?;
+    lib_dir.ancestors().skip(1).take(2).find_map(|dir| {
+        let include_dir = dir.join("include");
+        if include_dir.join("boost").join("version.hpp").is_file() {
+            Some(include_dir)
+        } else if dir.join("boost").join("version.hpp").is_file() {
+            Some(dir.to_owned())
+        } else {
+            None
+        }
+    })
+}
+
+fn has_boost_config(lib_dir: &Path) -> bool {
+    let entries = match fs::read_dir(lib_dir.join("cmake")) {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+    entries.flatten().any(|entry| {
+        entry.file_name().to_string_lossy().starts_with("Boost-")
+            && entry.path().join("BoostConfig.cmake").is_file()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|tag| (*tag).to_owned()).collect()
+    }
+
+    #[test]
+    fn parse_file_name_recognizes_layouts_and_tags() {
+        let cases = [
+            (
+                "libboost_context.a",
+                "context",
+                Some(("boost_context", LinkKind::Static, tags(&[]))),
+            ),
+            (
+                "libboost_context.so",
+                "context",
+                Some(("boost_context", LinkKind::Dylib, tags(&[]))),
+            ),
+            (
+                "libboost_context-mt.dylib",
+                "context",
+                Some(("boost_context-mt", LinkKind::Dylib, tags(&["mt"]))),
+            ),
+            (
+                "libboost_context-gcc11-mt-x64-1_79.a",
+                "context",
+                Some((
+                    "boost_context-gcc11-mt-x64-1_79",
+                    LinkKind::Static,
+                    tags(&["gcc11", "mt", "x64", "1_79"]),
+                )),
+            ),
+            (
+                "libboost_regex-mt-sgd.a",
+                "regex",
+                Some(("boost_regex-mt-sgd", LinkKind::Static, tags(&["mt", "sgd"]))),
+            ),
+            (
+                "libboost_regex-d.a",
+                "regex",
+                Some(("boost_regex-d", LinkKind::Static, tags(&["d"]))),
+            ),
+            (
+                "libboost_thread-vc143-mt-x64-1_82.lib",
+                "thread",
+                Some((
+                    "libboost_thread-vc143-mt-x64-1_82",
+                    LinkKind::Static,
+                    tags(&["vc143", "mt", "x64", "1_82"]),
+                )),
+            ),
+            (
+                "boost_thread-vc143-mt-x64-1_82.lib",
+                "thread",
+                Some((
+                    "boost_thread-vc143-mt-x64-1_82",
+                    LinkKind::Dylib,
+                    tags(&["vc143", "mt", "x64", "1_82"]),
+                )),
+            ),
+            ("libboost_thread_pool.a", "thread", None),
+            ("libboost_thread_pool-mt.a", "thread", None),
+            ("libboost_context.so.1.79.0", "context", None),
+            ("boost_context.a", "context", None),
+            ("libboost_context.a", "thread", None),
+            ("libboost_context.pc", "context", None),
+        ];
+        for (file_name, component, expected) in cases {
+            let expected = expected.map(|(name, kind, tags)| (name.to_owned(), kind, tags));
+            assert_eq!(
+                parse_file_name(file_name, component),
+                expected,
+                "{}",
+                file_name
+            );
+        }
+    }
+
+    #[test]
+    fn is_debug_recognizes_debug_abi_tags() {
+        let cases = [
+            (&["mt", "sgd"][..], true),
+            (&["d"], true),
+            (&["gd"], true),
+            (&["mt", "gd", "x64", "1_79"], true),
+            (&["mt"], false),
+            (&["s"], false),
+            (&["gcc11", "mt", "x64", "1_79"], false),
+            (&["vc143", "mt", "x64"], false),
+            (&[], false),
+        ];
+        for (abi_tags, expected) in cases {
+            assert_eq!(is_debug(&tags(abi_tags)), expected, "{:?}", abi_tags);
+        }
+    }
+
+    #[test]
+    fn resolve_ranks_kind_then_directory_then_release_then_plain_names() {
+        let root = env::temp_dir().join(format!("find-folly-boost-{}", std::process::id()));
+        let (first, second) = (root.join("first"), root.join("second"));
+        fs::create_dir_all(&first).unwrap();
+        fs::create_dir_all(&second).unwrap();
+        for path in [
+            first.join("libboost_context.so"),
+            first.join("libboost_regex-mt-d.a"),
+            first.join("libboost_regex-mt.a"),
+            first.join("libboost_regex.a"),
+            first.join("libboost_filesystem-d.a"),
+            first.join("libboost_filesystem-mt.a"),
+            first.join("libboost_thread-mt-sgd.a"),
+            second.join("libboost_context-mt.a"),
+            second.join("libboost_thread.a"),
+        ] {
+            fs::write(path, "").unwrap();
+        }
+        let resolver = |statik| BoostResolver {
+            search_dirs: vec![first.clone(), second.clone()],
+            system_dirs: vec![second.clone()],
+            statik,
+        };
+        let resolve = |statik, component| {
+            resolver(statik)
+                .resolve(component)
+                .map(|resolved| (resolved.library.name, resolved.search_dir))
+        };
+
+        // The wanted kind wins over an earlier directory.
+        assert_eq!(
+            resolve(true, "context"),
+            Some(("boost_context-mt".to_owned(), None))
+        );
+        assert_eq!(
+            resolve(false, "context"),
+            Some(("boost_context".to_owned(), Some(first.clone())))
+        );
+        // An earlier directory wins over a release build.
+        assert_eq!(
+            resolve(true, "thread"),
+            Some(("boost_thread-mt-sgd".to_owned(), Some(first.clone())))
+        );
+        // A release build wins over a plain name, and a plain name over a tagged one.
+        assert_eq!(
+            resolve(true, "filesystem"),
+            Some(("boost_filesystem-mt".to_owned(), Some(first.clone())))
+        );
+        assert_eq!(
+            resolve(true, "regex"),
+            Some(("boost_regex".to_owned(), Some(first.clone())))
+        );
+        assert_eq!(resolve(true, "system"), None);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
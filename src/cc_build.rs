@@ -0,0 +1,48 @@
+// find-folly/src/cc_build.rs
+//
+//! Configuring a [`cc::Build`] to compile C++ code against Folly. This module is only available
+//! with the `cc` feature.
+
+use crate::cflags;
+use crate::Folly;
+use std::env;
+
+impl Folly {
+    /// Configures `build` to compile C++ code that uses Folly.
+    ///
+    /// This turns on C++ mode and sets the C++ standard Folly was built with, or C++17 if that's
+    /// unknown. It adds Folly's include paths, defines, and other compiler flags. Except on MSVC,
+    /// the include paths are passed with `-isystem`, so that warnings in Folly's headers don't
+    /// drown out the ones in your own code.
+    ///
+    /// ```ignore
+    /// let folly = find_folly::probe_folly().unwrap();
+    /// folly.apply_to(cc::Build::new().file("src/shim.cpp")).compile("shim");
+    /// ```
+    pub fn apply_to<'a>(&self, build: &'a mut cc::Build) -> &'a mut cc::Build {
+        let msvc = env::var("CARGO_CFG_TARGET_ENV").is_ok_and(|target_env| target_env == "msvc");
+        build.cpp(true);
+        let cpp_std = self.cpp_std.as_deref().unwrap_or(cflags::DEFAULT_CPP_STD);
+        if msvc {
+            // MSVC has no GNU dialects.
+            build.std(&cpp_std.replace("gnu++", "c++"));
+        } else {
+            build.std(cpp_std);
+        }
+
+        for path in self.header_dirs() {
+            if msvc {
+                build.include(path);
+            } else {
+                build.flag("-isystem").flag(path.as_os_str());
+            }
+        }
+        for (name, value) in &self.defines {
+            build.define(name, value.as_deref());
+        }
+        for flag in &self.other_cflags {
+            build.flag(flag);
+        }
+        build
+    }
+}
@@ -0,0 +1,200 @@
+// find-folly/src/cflags.rs
+//
+//! Classification of the compiler flags that `pkg-config --cflags` reports for Folly.
+
+use crate::query;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+/// A single compiler flag, classified by what it does.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Cflag {
+    /// `-I<path>` or `-I <path>`.
+    Include(PathBuf),
+    /// `-isystem<path>` or `-isystem <path>`.
+    SystemInclude(PathBuf),
+    /// `-D<name>`, `-D<name>=<value>`, or the same with a space after `-D`.
+    Define(String, Option<String>),
+    /// `-std=<standard>`, for example `c++17`.
+    Std(String),
+    /// Anything else, such as `-pthread` or `-fno-omit-frame-pointer`. Flags that take a separate
+    /// argument are kept together, in order.
+    Other(Vec<String>),
+}
+
+/// The C++ standard assumed when Folly's flags don't name one. Folly requires at least C++17.
+pub(crate) const DEFAULT_CPP_STD: &str = "c++17";
+
+/// Header directories that compilers search without being told to. Passing one of these with
+/// `-isystem` moves it ahead of the C++ standard library's own directories, which breaks the
+/// library's `#include_next` directives, so they're left out of the flags altogether.
+pub(crate) const DEFAULT_INCLUDE_DIRS: &[&str] = &["/usr/include", "/usr/local/include"];
+
+// Flags that consume the following argument and that we don't otherwise understand. These have to
+// be kept next to their arguments, or the arguments will be misinterpreted.
+const FLAGS_WITH_ARGUMENT: &[&str] = &[
+    "-include",
+    "-imacros",
+    "-idirafter",
+    "-iquote",
+    "-isysroot",
+    "-iprefix",
+    "-iwithprefix",
+    "-Xclang",
+    "-Xpreprocessor",
+    "-x",
+];
+
+/// Classifies a list of already-tokenized compiler flags.
+///
+/// Include paths are kept as they are; everything else is converted to a string, lossily if
+/// necessary.
+pub(crate) fn classify_cflags<I>(args: I) -> Vec<Cflag>
+where
+    I: IntoIterator<Item = OsString>,
+{
+    let mut cflags = vec![];
+    let mut args = args.into_iter();
+    while let Some(os_arg) = args.next() {
+        // Check the flags that take paths first, since those must not be converted to strings.
+        if os_arg == "-I" || os_arg == "-isystem" {
+            let cflag = match args.next() {
+                Some(path) if os_arg == "-I" => Cflag::Include(PathBuf::from(path)),
+                Some(path) => Cflag::SystemInclude(PathBuf::from(path)),
+                None => Cflag::Other(vec![os_arg.to_string_lossy().into_owned()]),
+            };
+            cflags.push(cflag);
+            continue;
+        }
+        if let Some(path) = query::strip_flag(&os_arg, "-isystem") {
+            cflags.push(Cflag::SystemInclude(PathBuf::from(path)));
+            continue;
+        }
+        if let Some(path) = query::strip_flag(&os_arg, "-I") {
+            cflags.push(Cflag::Include(PathBuf::from(path)));
+            continue;
+        }
+
+        let arg = os_arg.to_string_lossy().into_owned();
+        let cflag = if arg == "-D" {
+            match args.next() {
+                Some(define) => parse_define(&define.to_string_lossy()),
+                None => Cflag::Other(vec![arg]),
+            }
+        } else if let Some(define) = arg.strip_prefix("-D") {
+            parse_define(define)
+        } else if let Some(std) = arg.strip_prefix("-std=") {
+            Cflag::Std(std.to_owned())
+        } else if FLAGS_WITH_ARGUMENT.contains(&&*arg) {
+            let mut flags = vec![arg];
+            flags.extend(args.next().map(|next| next.to_string_lossy().into_owned()));
+            Cflag::Other(flags)
+        } else {
+            Cflag::Other(vec![arg])
+        };
+        cflags.push(cflag);
+    }
+    cflags
+}
+
+fn parse_define(define: &str) -> Cflag {
+    match define.split_once('=') {
+        Some((name, value)) => Cflag::Define(name.to_owned(), Some(value.to_owned())),
+        None => Cflag::Define(define.to_owned(), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(args: &[&str]) -> Vec<Cflag> {
+        classify_cflags(args.iter().map(OsString::from))
+    }
+
+    fn other(args: &[&str]) -> Cflag {
+        Cflag::Other(args.iter().map(|arg| (*arg).to_owned()).collect())
+    }
+
+    #[test]
+    fn classify_cflags_recognizes_include_paths() {
+        assert_eq!(
+            classify(&[
+                "-I",
+                "/opt/a",
+                "-I/opt/b",
+                "-isystem",
+                "/opt/c",
+                "-isystem/opt/d"
+            ]),
+            [
+                Cflag::Include(PathBuf::from("/opt/a")),
+                Cflag::Include(PathBuf::from("/opt/b")),
+                Cflag::SystemInclude(PathBuf::from("/opt/c")),
+                Cflag::SystemInclude(PathBuf::from("/opt/d")),
+            ]
+        );
+    }
+
+    #[test]
+    fn classify_cflags_recognizes_defines_and_std() {
+        assert_eq!(
+            classify(&["-D", "X=1", "-DY", "-DZ=a=b", "-std=gnu++17"]),
+            [
+                Cflag::Define("X".to_owned(), Some("1".to_owned())),
+                Cflag::Define("Y".to_owned(), None),
+                Cflag::Define("Z".to_owned(), Some("a=b".to_owned())),
+                Cflag::Std("gnu++17".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn classify_cflags_keeps_flags_with_their_arguments() {
+        assert_eq!(
+            classify(&[
+                "-isysroot",
+                "/sdk",
+                "-include",
+                "x.h",
+                "-pthread",
+                "-x",
+                "c++"
+            ]),
+            [
+                other(&["-isysroot", "/sdk"]),
+                other(&["-include", "x.h"]),
+                other(&["-pthread"]),
+                other(&["-x", "c++"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn classify_cflags_keeps_flags_missing_their_arguments() {
+        assert_eq!(
+            classify(&["-pthread", "-I"]),
+            [other(&["-pthread"]), other(&["-I"])]
+        );
+        assert_eq!(classify(&["-isystem"]), [other(&["-isystem"])]);
+        assert_eq!(classify(&["-D"]), [other(&["-D"])]);
+        assert_eq!(classify(&["-include"]), [other(&["-include"])]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn classify_cflags_keeps_non_utf8_include_paths() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let path = OsString::from_vec(b"/opt/\xff".to_vec());
+        let mut flag = OsString::from("-I");
+        flag.push(&path);
+        assert_eq!(
+            classify_cflags([flag, OsString::from("-isystem"), path.clone()]),
+            [
+                Cflag::Include(PathBuf::from(&path)),
+                Cflag::SystemInclude(PathBuf::from(&path)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,115 @@
+// find-folly/src/cmake_config.rs
+//
+//! Pointing CMake projects built with the `cmake` crate at the Folly installation that was probed.
+//! This module is only available with the `cmake` feature.
+
+use crate::{link, pc_file, Folly};
+use std::path::{Path, PathBuf};
+
+// The dependencies whose package configurations Folly's own `folly-config.cmake` looks for.
+const DEPENDENCY_PACKAGES: &[&str] = &["fmt", "gflags", "glog"];
+
+impl Folly {
+    /// Configures `config` so that `find_package(folly)` in the CMake project finds the same Folly,
+    /// and the same dependencies, that this crate links against.
+    ///
+    /// This sets `CMAKE_PREFIX_PATH` to the installation prefixes of Folly and its dependencies,
+    /// `folly_DIR`, `fmt_DIR`, `gflags_DIR`, and `glog_DIR` to the package configuration
+    /// directories installed alongside their libraries, and `Boost_ROOT` to the prefix the Boost
+    /// libraries were found in. Anything that can't be determined is left unset, so CMake
+    /// searches for it as usual.
+    ///
+    /// ```ignore
+    /// let folly = find_folly::probe_folly().unwrap();
+    /// let dst = folly.configure_cmake(&mut cmake::Config::new("cpp")).build();
+    /// ```
+    pub fn configure_cmake<'a>(&self, config: &'a mut cmake::Config) -> &'a mut cmake::Config {
+        // CMake searches the system prefixes anyway.
+        let system_dirs = link::system_lib_dirs(self.sysroot.as_deref());
+        let lib_dirs: Vec<PathBuf> = self
+            .lib_dirs
+            .iter()
+            .filter(|lib_dir| !system_dirs.contains(lib_dir))
+            .cloned()
+            .collect();
+
+        let boost_root = self
+            .boost_libraries
+            .first()
+            .and_then(|library| library.path.parent())
+            .filter(|lib_dir| !system_dirs.iter().any(|dir| dir == lib_dir))
+            .and_then(|lib_dir| {
+                // A Boost source tree that was built in place keeps its libraries in `stage/lib`.
+                if lib_dir.parent()?.ends_with("stage") {
+                    lib_dir.parent()?.parent().map(ToOwned::to_owned)
+                } else {
+                    prefix_of(lib_dir)
+                }
+            });
+
+        let mut prefixes: Vec<PathBuf> = vec![];
+        for prefix in lib_dirs
+            .iter()
+            .filter_map(|lib_dir| prefix_of(lib_dir))
+            .chain(boost_root.clone())
+        {
+            if !prefixes.contains(&prefix) {
+                prefixes.push(prefix);
+            }
+        }
+        if !prefixes.is_empty() {
+            let prefixes: Vec<_> = prefixes
+                .iter()
+                .map(|prefix| prefix.to_string_lossy())
+                .collect();
+            config.define("CMAKE_PREFIX_PATH", prefixes.join(";"));
+        }
+
+        let folly_dir = self
+            .cmake_dir
+            .clone()
+            .or_else(|| config_dir_in(&lib_dirs, "folly"));
+        if let Some(folly_dir) = folly_dir {
+            config.define("folly_DIR", folly_dir);
+        }
+        for package in DEPENDENCY_PACKAGES {
+            if let Some(dir) = config_dir_in(&lib_dirs, package) {
+                config.define(format!("{}_DIR", package), dir);
+            }
+        }
+        if let Some(boost_root) = boost_root {
+            config.define("Boost_ROOT", boost_root);
+        }
+        config
+    }
+}
+
+// Finds the directory containing the CMake package configuration that `package` installs next to
+// its libraries in one of `lib_dirs`, such as `<libdir>/cmake/fmt`.
+fn config_dir_in(lib_dirs: &[PathBuf], package: &str) -> Option<PathBuf> {
+    let file_names = [
+        format!("{}-config.cmake", package),
+        format!("{}Config.cmake", package),
+    ];
+    lib_dirs
+        .iter()
+        .map(|lib_dir| lib_dir.join("cmake").join(package))
+        .find(|dir| file_names.iter().any(|name| dir.join(name).is_file()))
+}
+
+// Returns the installation prefix that `lib_dir` belongs to: `<prefix>` for `<prefix>/lib`,
+// `<prefix>/lib64`, or `<prefix>/lib/<multiarch>`.
+fn prefix_of(lib_dir: &Path) -> Option<PathBuf> {
+    let lib_dir = match pc_file::multiarch_triple() {
+        Some(multiarch) if lib_dir.ends_with(&multiarch) => lib_dir.parent()?,
+        _ => lib_dir,
+    };
+    if lib_dir
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with("lib"))
+    {
+        lib_dir.parent().map(Path::to_owned)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,769 @@
+// find-folly/src/cmake_package.rs
+//
+//! Locates Folly through the CMake package configuration it installs.
+//!
+//! Folly's CMake export (`folly-config.cmake` and `folly-targets.cmake`) is far more complete than
+//! its `.pc` file: the `INTERFACE_LINK_LIBRARIES` of the `Folly::folly` target lists `fmt`,
+//! `gflags`, `glog`, Boost, and everything else. We don't run CMake; instead, we interpret the
+//! small subset of the language that `install(EXPORT)` generates and translate the target
+//! properties into the same arguments `pkg-config` would print.
+
+use crate::pc_file;
+use crate::FollyError;
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Folly's compiler and linker flags, as recovered from its CMake export.
+pub(crate) struct CMakePackage {
+    /// The directory containing `folly-config.cmake`.
+    pub(crate) config_dir: PathBuf,
+    /// Equivalent of `pkg-config --libs`.
+    pub(crate) libs: Vec<OsString>,
+    /// Equivalent of `pkg-config --cflags`.
+    pub(crate) cflags: Vec<OsString>,
+    /// The `PACKAGE_VERSION` from `folly-config-version.cmake`, if present.
+    pub(crate) version: Option<String>,
+    /// Every file that was read.
+    pub(crate) files: Vec<PathBuf>,
+}
+
+/// The environment variables that affect where the package configuration is found.
+pub(crate) const ENV_VARS: &[&str] = &["folly_DIR", "CMAKE_PREFIX_PATH"];
+
+const CONFIG_FILE_NAMES: &[&str] = &["folly-config.cmake", "follyConfig.cmake"];
+const VERSION_FILE_NAMES: &[&str] = &["folly-config-version.cmake", "follyConfigVersion.cmake"];
+const FOLLY_TARGET: &str = "Folly::folly";
+
+// The configurations whose imported locations we're willing to use, most preferred first. The
+// empty string stands for the configuration-independent property.
+const CONFIGURATIONS: &[&str] = &[
+    "RELEASE",
+    "RELWITHDEBINFO",
+    "MINSIZEREL",
+    "NOCONFIG",
+    "",
+    "DEBUG",
+];
+
+// Imported targets from other packages that don't follow the `Namespace::library` convention.
+const KNOWN_TARGETS: &[(&str, &[&str])] = &[
+    ("Threads::Threads", &["-pthread"]),
+    ("ZLIB::ZLIB", &["-lz"]),
+    ("BZip2::BZip2", &["-lbz2"]),
+    ("LibLZMA::LibLZMA", &["-llzma"]),
+    ("OpenSSL::SSL", &["-lssl"]),
+    ("OpenSSL::Crypto", &["-lcrypto"]),
+    ("fmt::fmt-header-only", &[]),
+    ("gflags_shared", &["-lgflags"]),
+    ("gflags_static", &["-lgflags"]),
+    ("gflags_nothreads_static", &["-lgflags_nothreads"]),
+    ("glog::glog", &["-lglog"]),
+    ("Libevent::core", &["-levent_core"]),
+    ("Libevent::extra", &["-levent_extra"]),
+    ("Libevent::event", &["-levent"]),
+];
+
+/// Returns the directories, under each installation prefix, where `folly-config.cmake` may live.
+pub(crate) fn config_dirs_under(prefix: &Path) -> Vec<PathBuf> {
+    let mut lib_dirs = vec!["lib".to_owned(), "lib64".to_owned()];
+    if let Some(multiarch) = pc_file::multiarch_triple() {
+        lib_dirs.push(format!("lib/{}", multiarch));
+    }
+    let mut dirs: Vec<_> = lib_dirs
+        .iter()
+        .map(|lib_dir| prefix.join(lib_dir).join("cmake").join("folly"))
+        .collect();
+    dirs.push(prefix.join("share").join("folly"));
+    dirs
+}
+
+/// Searches `folly_DIR`, `CMAKE_PREFIX_PATH`, and the usual installation prefixes for Folly's
+/// CMake package configuration, and reads it.
+pub(crate) fn find(statik: bool) -> Result<CMakePackage, FollyError> {
+    let mut search_dirs = vec![];
+    if let Some(dir) = env::var_os("folly_DIR") {
+        search_dirs.push(PathBuf::from(dir));
+    }
+    let mut prefixes = vec![];
+    if let Some(prefix_path) = env::var_os("CMAKE_PREFIX_PATH") {
+        prefixes.extend(env::split_paths(&prefix_path));
+    }
+    prefixes.extend(
+        ["/usr/local", "/usr", "/opt/homebrew"]
+            .iter()
+            .map(PathBuf::from),
+    );
+    for prefix in &prefixes {
+        search_dirs.extend(config_dirs_under(prefix));
+    }
+    find_in(&search_dirs, statik)
+}
+
+/// Reads Folly's CMake package configuration from the first of `search_dirs` that contains it.
+pub(crate) fn find_in(search_dirs: &[PathBuf], statik: bool) -> Result<CMakePackage, FollyError> {
+    let config_path = search_dirs
+        .iter()
+        .flat_map(|dir| CONFIG_FILE_NAMES.iter().map(move |name| dir.join(name)))
+        .find(|path| path.is_file())
+        .ok_or_else(|| FollyError::CMakePackageNotFound {
+            search_dirs: search_dirs.to_vec(),
+        })?;
+    read(&config_path, statik)
+}
+
+fn read(config_path: &Path, statik: bool) -> Result<CMakePackage, FollyError> {
+    let config_dir = config_path.parent().unwrap_or(Path::new("."));
+    // The targets file defines the targets; the per-configuration files next to it fill in where
+    // the built libraries are.
+    let targets_path = config_dir.join("folly-targets.cmake");
+    let mut paths = vec![targets_path.clone()];
+    let mut configuration_paths = vec![];
+    let entries = fs::read_dir(config_dir)
+        .map_err(|error| FollyError::ReadFile(config_dir.to_owned(), error))?;
+    for entry in entries.flatten() {
+        let file_name = entry.file_name();
+        let file_name = file_name.to_string_lossy();
+        if file_name.starts_with("folly-targets-") && file_name.ends_with(".cmake") {
+            configuration_paths.push(entry.path());
+        }
+    }
+    configuration_paths.sort();
+    paths.extend(configuration_paths);
+
+    let mut interpreter = Interpreter::default();
+    for path in &paths {
+        let contents = fs::read(path).map_err(|error| FollyError::ReadFile(path.clone(), error))?;
+        interpreter.run(&String::from_utf8_lossy(&contents), path);
+    }
+    if !interpreter.targets.contains_key(FOLLY_TARGET) {
+        return Err(FollyError::CMakeTarget(targets_path));
+    }
+
+    // The version file is only used by `find_package()` to check version requirements, but it's
+    // the only place the version is recorded.
+    let mut version = None;
+    if let Some(version_path) = VERSION_FILE_NAMES
+        .iter()
+        .map(|name| config_dir.join(name))
+        .find(|path| path.is_file())
+    {
+        let contents = fs::read(&version_path)
+            .map_err(|error| FollyError::ReadFile(version_path.clone(), error))?;
+        let mut version_interpreter = Interpreter::default();
+        version_interpreter.run(&String::from_utf8_lossy(&contents), &version_path);
+        version = version_interpreter.variables.remove("PACKAGE_VERSION");
+        paths.push(version_path);
+    }
+
+    let mut package = CMakePackage {
+        config_dir: config_dir.to_owned(),
+        libs: vec![],
+        cflags: vec![],
+        version,
+        files: [config_path.to_owned()].into_iter().chain(paths).collect(),
+    };
+    let mut visited = vec![];
+    interpreter.collect(FOLLY_TARGET, statik, &mut visited, &mut package);
+    Ok(package)
+}
+
+// An imported target and its properties.
+#[derive(Default)]
+struct Target {
+    properties: HashMap<String, String>,
+}
+
+// Just enough of a CMake interpreter to evaluate generated export files.
+#[derive(Default)]
+struct Interpreter {
+    variables: HashMap<String, String>,
+    targets: HashMap<String, Target>,
+}
+
+impl Interpreter {
+    fn run(&mut self, source: &str, path: &Path) {
+        self.variables.insert(
+            "CMAKE_CURRENT_LIST_FILE".to_owned(),
+            path.to_string_lossy().into_owned(),
+        );
+        if let Some(dir) = path.parent() {
+            self.variables.insert(
+                "CMAKE_CURRENT_LIST_DIR".to_owned(),
+                dir.to_string_lossy().into_owned(),
+            );
+        }
+
+        for (command, args) in parse_commands(source) {
+            // Expand variables with the values they have at this point in the file.
+            let args: Vec<String> = args
+                .into_iter()
+                .map(|arg| expand_variables(&arg, &self.variables))
+                .collect();
+            match (&*command.to_ascii_lowercase(), &args[..]) {
+                ("set", [name, value, ..]) if !value.is_empty() => {
+                    // Export files reset `_IMPORT_PREFIX` to the empty string inside an `if` that
+                    // we don't evaluate, so ignore empty assignments.
+                    self.variables.insert(name.clone(), value.clone());
+                }
+                ("get_filename_component", [name, input, mode, ..])
+                    if mode == "PATH" || mode == "DIRECTORY" =>
+                {
+                    let parent = Path::new(input).parent().unwrap_or_else(|| Path::new(""));
+                    self.variables
+                        .insert(name.clone(), parent.to_string_lossy().into_owned());
+                }
+                ("add_library", [name, ..]) => {
+                    self.targets.entry(name.clone()).or_default();
+                }
+                ("set_target_properties", args) => {
+                    let properties_index = match args.iter().position(|arg| arg == "PROPERTIES") {
+                        Some(index) => index,
+                        None => continue,
+                    };
+                    for name in &args[..properties_index] {
+                        let target = self.targets.entry(name.clone()).or_default();
+                        for pair in args[properties_index + 1..].chunks(2) {
+                            if let [key, value] = pair {
+                                target.properties.insert(key.clone(), value.clone());
+                            }
+                        }
+                    }
+                }
+                ("set_property", [kind, name, rest @ ..]) if kind == "TARGET" => {
+                    let append = rest.first().is_some_and(|arg| arg == "APPEND");
+                    let rest = if append { &rest[1..] } else { rest };
+                    if let [property, key, values @ ..] = rest {
+                        if property != "PROPERTY" {
+                            continue;
+                        }
+                        let target = self.targets.entry(name.clone()).or_default();
+                        let value = target.properties.entry(key.clone()).or_default();
+                        if !append {
+                            value.clear();
+                        }
+                        for new_value in values {
+                            if !value.is_empty() {
+                                value.push(';');
+                            }
+                            value.push_str(new_value);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Appends the flags for `name` and everything it links against to `package`.
+    fn collect(
+        &self,
+        name: &str,
+        statik: bool,
+        visited: &mut Vec<String>,
+        package: &mut CMakePackage,
+    ) {
+        if visited.iter().any(|visited_name| visited_name == name) {
+            return;
+        }
+        visited.push(name.to_owned());
+
+        let target = &self.targets[name];
+        let property = |key: &str| -> Vec<String> {
+            target
+                .properties
+                .get(key)
+                .map(|value| split_list(&evaluate_generator_expressions(value, statik)))
+                .unwrap_or_default()
+        };
+
+        for dir in property("INTERFACE_INCLUDE_DIRECTORIES") {
+            package.cflags.push(format!("-I{}", dir).into());
+        }
+        for dir in property("INTERFACE_SYSTEM_INCLUDE_DIRECTORIES") {
+            package.cflags.push("-isystem".into());
+            package.cflags.push(dir.into());
+        }
+        for define in property("INTERFACE_COMPILE_DEFINITIONS") {
+            package.cflags.push(format!("-D{}", define).into());
+        }
+        for option in property("INTERFACE_COMPILE_OPTIONS") {
+            package.cflags.push(option.into());
+        }
+
+        let location = CONFIGURATIONS.iter().find_map(|configuration| {
+            let suffix = if configuration.is_empty() {
+                String::new()
+            } else {
+                format!("_{}", configuration)
+            };
+            target
+                .properties
+                .get(&format!("IMPORTED_LOCATION{}", suffix))
+                .cloned()
+        });
+        if let Some(location) = location {
+            let location = PathBuf::from(location);
+            if let Some(dir) = location.parent() {
+                let mut search_flag = OsString::from("-L");
+                search_flag.push(dir);
+                package.libs.push(search_flag);
+            }
+            package.libs.push(location.into());
+        }
+
+        let mut link_libraries = property("INTERFACE_LINK_LIBRARIES");
+        for configuration in CONFIGURATIONS.iter().filter(|name| !name.is_empty()) {
+            let key = format!("IMPORTED_LINK_INTERFACE_LIBRARIES_{}", configuration);
+            if target.properties.contains_key(&key) {
+                link_libraries.extend(property(&key));
+                break;
+            }
+        }
+        for library in link_libraries {
+            if self.targets.contains_key(&library) {
+                self.collect(&library, statik, visited, package);
+            } else {
+                package.libs.extend(link_library_args(&library));
+            }
+        }
+    }
+}
+
+// Translates one entry of `INTERFACE_LINK_LIBRARIES` that isn't defined in Folly's own export.
+fn link_library_args(library: &str) -> Vec<OsString> {
+    if let Some(&(_, args)) = KNOWN_TARGETS.iter().find(|(name, _)| *name == library) {
+        return args.iter().map(OsString::from).collect();
+    }
+    if library.starts_with('-') || Path::new(library).is_absolute() {
+        return vec![library.into()];
+    }
+    // `Boost::context` becomes `-lboost_context`, `fmt::fmt` becomes `-lfmt`, and so on.
+    let name = match library.split_once("::") {
+        Some((namespace, name)) if namespace.eq_ignore_ascii_case("boost") => {
+            format!("boost_{}", name)
+        }
+        Some((_, name)) => name.to_owned(),
+        None => library.to_owned(),
+    };
+    vec![format!("-l{}", name).into()]
+}
+
+// Splits a CMake list on semicolons, ignoring empty elements.
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(';')
+        .filter(|element| !element.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+// Evaluates the generator expressions that appear in export files, assuming a release build of
+// C++ code with GCC or Clang.
+fn evaluate_generator_expressions(value: &str, statik: bool) -> String {
+    let mut result = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("$<") {
+        result.push_str(&rest[..start]);
+        let end = match matching_angle_bracket(&rest[start..]) {
+            Some(end) => start + end,
+            None => {
+                // Unterminated; keep it verbatim.
+                result.push_str(&rest[start..]);
+                return result;
+            }
+        };
+        result.push_str(&evaluate_generator_expression(
+            &rest[start + 2..end],
+            statik,
+        ));
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+fn evaluate_generator_expression(expression: &str, statik: bool) -> String {
+    // Conditions may themselves be generator expressions, so split at the first colon that isn't
+    // nested.
+    let (head, arg) = match top_level_colon(expression) {
+        Some(colon) => (&expression[..colon], &expression[colon + 1..]),
+        None => (expression, ""),
+    };
+    let head = evaluate_generator_expressions(head, statik);
+    let arg = || evaluate_generator_expressions(arg, statik);
+    let is_msvc = env::var("TARGET").is_ok_and(|target| target.ends_with("-msvc"));
+    match &*head {
+        "1" | "INSTALL_INTERFACE" => arg(),
+        "0" | "BUILD_INTERFACE" => String::new(),
+        // `LINK_ONLY` marks the private dependencies of a static library, which only need to be
+        // linked when Folly itself is linked statically, like `Libs.private`.
+        "LINK_ONLY" if statik => arg(),
+        "LINK_ONLY" => String::new(),
+        "NOT" => bool_string(arg() == "0"),
+        "BOOL" => bool_string(!matches!(&*arg(), "" | "0" | "OFF" | "FALSE" | "NO" | "N")),
+        "AND" => bool_string(arg().split(',').all(|value| value == "1")),
+        "OR" => bool_string(arg().split(',').any(|value| value == "1")),
+        "CONFIG" => bool_string(
+            arg()
+                .split(',')
+                .any(|configuration| configuration.eq_ignore_ascii_case("Release")),
+        ),
+        "COMPILE_LANGUAGE" => bool_string(arg().split(',').any(|language| language == "CXX")),
+        "CXX_COMPILER_ID" | "C_COMPILER_ID" => bool_string(arg().split(',').any(|id| {
+            if is_msvc {
+                id == "MSVC"
+            } else {
+                matches!(id, "GNU" | "Clang" | "AppleClang")
+            }
+        })),
+        _ => String::new(),
+    }
+}
+
+fn bool_string(value: bool) -> String {
+    if value { "1" } else { "0" }.to_owned()
+}
+
+// Given a string starting with `$<`, returns the index of the matching `>`.
+fn matching_angle_bracket(expression: &str) -> Option<usize> {
+    let bytes = expression.as_bytes();
+    let mut depth = 0;
+    for (index, &byte) in bytes.iter().enumerate() {
+        if byte == b'<' && index > 0 && bytes[index - 1] == b'$' {
+            depth += 1;
+        } else if byte == b'>' {
+            depth -= 1;
+            if depth == 0 {
+                return Some(index);
+            }
+        }
+    }
+    None
+}
+
+fn top_level_colon(expression: &str) -> Option<usize> {
+    let bytes = expression.as_bytes();
+    let mut depth = 0;
+    for (index, &byte) in bytes.iter().enumerate() {
+        match byte {
+            b'<' if index > 0 && bytes[index - 1] == b'$' => depth += 1,
+            b'>' if depth > 0 => depth -= 1,
+            b':' if depth == 0 => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+// Replaces `${NAME}` with the value of `NAME`, or the empty string if it's undefined. Generated
+// export files escape literal dollar signs, which `parse_commands` has already turned into
+// `\u{0}` so that they aren't mistaken for variable references here.
+fn expand_variables(arg: &str, variables: &HashMap<String, String>) -> String {
+    let mut expanded = String::new();
+    let mut rest = arg;
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        match rest[start..].find('}') {
+            Some(end) => {
+                let name = &rest[start + 2..start + end];
+                expanded.push_str(variables.get(name).map_or("", |value| value));
+                rest = &rest[start + end + 1..];
+            }
+            None => {
+                expanded.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    expanded.push_str(rest);
+    expanded.replace('\u{0}', "$")
+}
+
+// Splits CMake source into commands and their arguments. Quoted arguments are unescaped, but
+// variable references are left for `expand_variables`.
+fn parse_commands(source: &str) -> Vec<(String, Vec<String>)> {
+    let mut commands = vec![];
+    let mut chars = source.chars().peekable();
+    loop {
+        // Skip whitespace and comments between commands.
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+            } else if c == '#' {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+
+        let mut name = String::new();
+        while let Some(&c) = chars.peek() {
+            if c.is_alphanumeric() || c == '_' {
+                name.push(c);
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        if name.is_empty() {
+            // Either the end of the file or something we don't understand; skip a character and
+            // resynchronize.
+            if chars.next().is_none() {
+                break;
+            }
+            continue;
+        }
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        if chars.peek() != Some(&'(') {
+            continue;
+        }
+        chars.next();
+
+        let mut args = vec![];
+        let mut depth = 0;
+        let mut current: Option<String> = None;
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => {
+                    let mut arg = String::new();
+                    while let Some(c) = chars.next() {
+                        match c {
+                            '"' => break,
+                            '\\' => match chars.next() {
+                                Some('$') => arg.push('\u{0}'),
+                                Some('n') => arg.push('\n'),
+                                Some('t') => arg.push('\t'),
+                                Some('\n') | None => {}
+                                Some(other) => arg.push(other),
+                            },
+                            _ => arg.push(c),
+                        }
+                    }
+                    current.get_or_insert_with(String::new).push_str(&arg);
+                }
+                '#' if current.is_none() => {
+                    for c in chars.by_ref() {
+                        if c == '\n' {
+                            break;
+                        }
+                    }
+                }
+                '(' => {
+                    depth += 1;
+                    current.get_or_insert_with(String::new).push(c);
+                }
+                ')' if depth == 0 => break,
+                ')' => {
+                    depth -= 1;
+                    current.get_or_insert_with(String::new).push(c);
+                }
+                c if c.is_whitespace() => args.extend(current.take()),
+                '\\' => match chars.next() {
+                    Some('$') => current.get_or_insert_with(String::new).push('\u{0}'),
+                    Some(other) => current.get_or_insert_with(String::new).push(other),
+                    None => {}
+                },
+                _ => current.get_or_insert_with(String::new).push(c),
+            }
+        }
+        args.extend(current.take());
+        commands.push((name, args));
+    }
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(library: &str) -> Vec<String> {
+        link_library_args(library)
+            .into_iter()
+            .map(|arg| arg.into_string().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn parse_commands_splits_arguments() {
+        let commands = parse_commands(
+            "# Generated by CMake\n\
+             set(_IMPORT_PREFIX \"/opt/folly\")\n\
+             set_target_properties(Folly::folly PROPERTIES\n  \
+               INTERFACE_INCLUDE_DIRECTORIES \"${_IMPORT_PREFIX}/include\" # trailing\n\
+             )\n\
+             if (NOT (A AND B))\n\
+             add_library (Folly::folly STATIC IMPORTED)\n",
+        );
+        assert_eq!(
+            commands,
+            [
+                (
+                    "set".to_owned(),
+                    vec!["_IMPORT_PREFIX".to_owned(), "/opt/folly".to_owned()]
+                ),
+                (
+                    "set_target_properties".to_owned(),
+                    vec![
+                        "Folly::folly".to_owned(),
+                        "PROPERTIES".to_owned(),
+                        "INTERFACE_INCLUDE_DIRECTORIES".to_owned(),
+                        "${_IMPORT_PREFIX}/include".to_owned(),
+                    ]
+                ),
+                (
+                    "if".to_owned(),
+                    vec![
+                        "NOT".to_owned(),
+                        "(A".to_owned(),
+                        "AND".to_owned(),
+                        "B)".to_owned()
+                    ]
+                ),
+                (
+                    "add_library".to_owned(),
+                    vec![
+                        "Folly::folly".to_owned(),
+                        "STATIC".to_owned(),
+                        "IMPORTED".to_owned()
+                    ]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_commands_unescapes_quoted_arguments() {
+        let commands = parse_commands("message(\"a\\\"b\\n\" \"\" \\${NOT_A_VARIABLE})");
+        assert_eq!(commands[0].1, ["a\"b\n", "", "\u{0}{NOT_A_VARIABLE}"]);
+        let variables = HashMap::new();
+        assert_eq!(
+            expand_variables(&commands[0].1[2], &variables),
+            "${NOT_A_VARIABLE}"
+        );
+    }
+
+    #[test]
+    fn expand_variables_uses_defined_values() {
+        let variables = HashMap::from([("PREFIX".to_owned(), "/opt/folly".to_owned())]);
+        assert_eq!(
+            expand_variables("${PREFIX}/lib;${UNDEFINED}x", &variables),
+            "/opt/folly/lib;x"
+        );
+        assert_eq!(expand_variables("${PREFIX", &variables), "${PREFIX");
+    }
+
+    #[test]
+    fn interpreter_collects_target_properties() {
+        let mut interpreter = Interpreter::default();
+        interpreter.run(
+            "get_filename_component(_IMPORT_PREFIX \"${CMAKE_CURRENT_LIST_FILE}\" PATH)\n\
+             get_filename_component(_IMPORT_PREFIX \"${_IMPORT_PREFIX}\" PATH)\n\
+             add_library(Folly::folly STATIC IMPORTED)\n\
+             set_target_properties(Folly::folly PROPERTIES\n  \
+               INTERFACE_INCLUDE_DIRECTORIES \"${_IMPORT_PREFIX}/include\"\n  \
+               INTERFACE_LINK_LIBRARIES \"fmt::fmt;$<LINK_ONLY:gflags_static>\"\n\
+             )\n\
+             set_property(TARGET Folly::folly APPEND PROPERTY INTERFACE_COMPILE_DEFINITIONS A B)\n\
+             set_target_properties(Folly::folly PROPERTIES\n  \
+               IMPORTED_LOCATION_RELEASE \"${_IMPORT_PREFIX}/lib/libfolly.a\"\n\
+             )\n",
+            Path::new("/opt/folly/cmake/folly-targets.cmake"),
+        );
+        let mut package = CMakePackage {
+            config_dir: PathBuf::new(),
+            libs: vec![],
+            cflags: vec![],
+            version: None,
+            files: vec![],
+        };
+        interpreter.collect(FOLLY_TARGET, true, &mut vec![], &mut package);
+        assert_eq!(package.cflags, ["-I/opt/folly/include", "-DA", "-DB"]);
+        assert_eq!(
+            package.libs,
+            [
+                "-L/opt/folly/lib",
+                "/opt/folly/lib/libfolly.a",
+                "-lfmt",
+                "-lgflags"
+            ]
+        );
+    }
+
+    #[test]
+    fn evaluate_generator_expressions_selects_release_cxx_values() {
+        let evaluate = |value| evaluate_generator_expressions(value, true);
+        assert_eq!(evaluate("$<$<CONFIG:Release>:-DNDEBUG>"), "-DNDEBUG");
+        assert_eq!(evaluate("$<$<CONFIG:Debug>:-DDEBUG>"), "");
+        assert_eq!(evaluate("$<$<CONFIG:Debug,RELEASE>:x>"), "x");
+        assert_eq!(
+            evaluate("$<$<COMPILE_LANGUAGE:CXX>:-std=c++17>;$<$<COMPILE_LANGUAGE:C>:-std=c11>"),
+            "-std=c++17;"
+        );
+        assert_eq!(evaluate("$<$<NOT:$<CONFIG:Debug>>:-O2>"), "-O2");
+        assert_eq!(
+            evaluate("$<$<AND:$<CONFIG:Release>,$<BOOL:ON>>:both>"),
+            "both"
+        );
+        assert_eq!(evaluate("$<$<OR:0,$<BOOL:OFF>>:either>"), "");
+    }
+
+    #[test]
+    fn evaluate_generator_expressions_handles_interfaces() {
+        assert_eq!(
+            evaluate_generator_expressions(
+                "$<BUILD_INTERFACE:/src/folly>;$<INSTALL_INTERFACE:include>",
+                false
+            ),
+            ";include"
+        );
+    }
+
+    #[test]
+    fn evaluate_generator_expressions_links_private_libraries_statically() {
+        let value = "fmt::fmt;$<LINK_ONLY:glog::glog>;$<LINK_ONLY:$<$<CONFIG:Release>:-ldl>>";
+        assert_eq!(
+            evaluate_generator_expressions(value, true),
+            "fmt::fmt;glog::glog;-ldl"
+        );
+        assert_eq!(evaluate_generator_expressions(value, false), "fmt::fmt;;");
+    }
+
+    #[test]
+    fn evaluate_generator_expressions_keeps_unterminated_expressions() {
+        assert_eq!(
+            evaluate_generator_expressions("a;$<CONFIG:Release", true),
+            "a;$<CONFIG:Release"
+        );
+        assert_eq!(evaluate_generator_expressions("$<UNKNOWN:x>", true), "");
+    }
+
+    #[test]
+    fn link_library_args_translates_targets() {
+        assert_eq!(args("Threads::Threads"), ["-pthread"]);
+        assert_eq!(args("gflags_static"), ["-lgflags"]);
+        assert!(args("fmt::fmt-header-only").is_empty());
+        assert_eq!(args("Boost::context"), ["-lboost_context"]);
+        assert_eq!(args("boost::filesystem"), ["-lboost_filesystem"]);
+        assert_eq!(args("fmt::fmt"), ["-lfmt"]);
+        assert_eq!(args("dl"), ["-ldl"]);
+        assert_eq!(args("-Wl,--as-needed"), ["-Wl,--as-needed"]);
+        assert_eq!(
+            args("/usr/lib/libdouble-conversion.so"),
+            ["/usr/lib/libdouble-conversion.so"]
+        );
+    }
+
+    #[test]
+    fn split_list_ignores_empty_elements() {
+        assert_eq!(split_list(";a;;b;"), ["a", "b"]);
+    }
+}
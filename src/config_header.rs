@@ -0,0 +1,202 @@
+// find-folly/src/config_header.rs
+//
+//! Reading `folly/folly-config.h`, the header that records how Folly was configured.
+//!
+//! The header is generated by CMake and consists of `#define` and `/* #undef */` lines, so there's
+//! no need for a real preprocessor.
+
+use crate::FollyError;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// The optional features and dependencies that Folly was built with, as recorded in
+/// `folly/folly-config.h`.
+///
+/// Each field is true if the corresponding macro is defined to anything other than `0`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FollyFeatures {
+    /// `FOLLY_HAVE_LIBGFLAGS`: command-line flags are provided by gflags.
+    pub gflags: bool,
+    /// `FOLLY_HAVE_LIBGLOG`: logging is provided by glog.
+    pub glog: bool,
+    /// `FOLLY_USE_JEMALLOC`: Folly assumes jemalloc is the allocator.
+    pub jemalloc: bool,
+    /// `FOLLY_HAVE_LIBURING`: the io_uring backends are available.
+    pub uring: bool,
+    /// `FOLLY_HAVE_LIBAIO`: the libaio backend is available.
+    pub libaio: bool,
+    /// `FOLLY_HAVE_LIBUNWIND`: stack traces use libunwind.
+    pub libunwind: bool,
+    /// `FOLLY_HAVE_DWARF`: symbolization uses libdwarf.
+    pub libdwarf: bool,
+    /// `FOLLY_HAVE_ELF`: ELF symbol lookup is available.
+    pub elf: bool,
+    /// `FOLLY_HAVE_LIBSODIUM`: libsodium is available.
+    pub sodium: bool,
+    /// `FOLLY_HAVE_LIBZ`: zlib compression is available.
+    pub zlib: bool,
+    /// `FOLLY_HAVE_LIBZSTD`: zstd compression is available.
+    pub zstd: bool,
+    /// `FOLLY_HAVE_LIBLZ4`: LZ4 compression is available.
+    pub lz4: bool,
+    /// `FOLLY_HAVE_LIBSNAPPY`: Snappy compression is available.
+    pub snappy: bool,
+    /// `FOLLY_HAVE_LIBLZMA`: LZMA compression is available.
+    pub lzma: bool,
+    /// `FOLLY_HAVE_LIBBZ2`: bzip2 compression is available.
+    pub bz2: bool,
+    /// `FOLLY_HAS_COROUTINES`, or failing that, whether Folly was compiled with coroutine support
+    /// enabled (`-fcoroutines` or C++20 and later): `folly::coro` is available.
+    pub coroutines: bool,
+}
+
+impl FollyFeatures {
+    /// Returns each feature's short name, as used in `folly_has_<name>` cfgs, and whether it's
+    /// enabled.
+    pub(crate) fn by_name(&self) -> [(&'static str, bool); 16] {
+        [
+            ("gflags", self.gflags),
+            ("glog", self.glog),
+            ("jemalloc", self.jemalloc),
+            ("uring", self.uring),
+            ("libaio", self.libaio),
+            ("libunwind", self.libunwind),
+            ("libdwarf", self.libdwarf),
+            ("elf", self.elf),
+            ("sodium", self.sodium),
+            ("zlib", self.zlib),
+            ("zstd", self.zstd),
+            ("lz4", self.lz4),
+            ("snappy", self.snappy),
+            ("lzma", self.lzma),
+            ("bz2", self.bz2),
+            ("coro", self.coroutines),
+        ]
+    }
+}
+
+/// The macros defined in `folly-config.h`.
+pub(crate) struct ConfigHeader {
+    defines: HashMap<String, String>,
+}
+
+impl ConfigHeader {
+    pub(crate) fn read(path: &Path) -> Result<ConfigHeader, FollyError> {
+        let contents =
+            fs::read(path).map_err(|error| FollyError::ReadFile(path.to_owned(), error))?;
+        Ok(ConfigHeader::parse(&String::from_utf8_lossy(&contents)))
+    }
+
+    fn parse(contents: &str) -> ConfigHeader {
+        let mut defines = HashMap::new();
+        for line in contents.lines() {
+            let line = line.trim();
+            let rest = match line
+                .strip_prefix('#')
+                .map(str::trim_start)
+                .and_then(|line| line.strip_prefix("define"))
+            {
+                Some(rest) if rest.starts_with(char::is_whitespace) => rest.trim(),
+                _ => continue,
+            };
+            let (name, value) = match rest.split_once(char::is_whitespace) {
+                Some((name, value)) => (name, value.trim()),
+                None => (rest, ""),
+            };
+            // Function-like macros aren't configuration.
+            if !name.contains('(') {
+                defines.insert(name.to_owned(), value.to_owned());
+            }
+        }
+        ConfigHeader { defines }
+    }
+
+    /// Returns the features recorded in the header. Coroutine support is only filled in if the
+    /// header records it explicitly.
+    pub(crate) fn features(&self) -> FollyFeatures {
+        FollyFeatures {
+            gflags: self.is_set("FOLLY_HAVE_LIBGFLAGS"),
+            glog: self.is_set("FOLLY_HAVE_LIBGLOG"),
+            jemalloc: self.is_set("FOLLY_USE_JEMALLOC"),
+            uring: self.is_set("FOLLY_HAVE_LIBURING"),
+            libaio: self.is_set("FOLLY_HAVE_LIBAIO"),
+            libunwind: self.is_set("FOLLY_HAVE_LIBUNWIND"),
+            libdwarf: self.is_set("FOLLY_HAVE_DWARF"),
+            elf: self.is_set("FOLLY_HAVE_ELF"),
+            sodium: self.is_set("FOLLY_HAVE_LIBSODIUM"),
+            zlib: self.is_set("FOLLY_HAVE_LIBZ"),
+            zstd: self.is_set("FOLLY_HAVE_LIBZSTD"),
+            lz4: self.is_set("FOLLY_HAVE_LIBLZ4"),
+            snappy: self.is_set("FOLLY_HAVE_LIBSNAPPY"),
+            lzma: self.is_set("FOLLY_HAVE_LIBLZMA"),
+            bz2: self.is_set("FOLLY_HAVE_LIBBZ2"),
+            coroutines: self.is_set("FOLLY_HAS_COROUTINES"),
+        }
+    }
+
+    /// Returns true if `name` is defined to anything other than `0`.
+    ///
+    /// `#cmakedefine01` writes `0` for disabled features, and `#cmakedefine` comments them out.
+    pub(crate) fn is_set(&self, name: &str) -> bool {
+        self.defines.get(name).is_some_and(|value| value != "0")
+    }
+
+    /// Returns the value of `FOLLY_VERSION`, without the quotes.
+    pub(crate) fn version(&self) -> Option<&str> {
+        self.defines
+            .get("FOLLY_VERSION")
+            .map(|value| value.trim_matches('"'))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_defines() {
+        let header = ConfigHeader::parse(
+            "#pragma once\n\
+             #define FOLLY_HAVE_LIBGFLAGS 1\n\
+             /* #undef FOLLY_HAVE_LIBURING */\n\
+             #  define FOLLY_USE_JEMALLOC 1\n\
+             \t#define FOLLY_HAVE_LIBAIO\n\
+             #define FOLLY_HAVE_LIBZSTD 0\n\
+             #defineFOLLY_HAVE_LIBLZ4 1\n\
+             #define FOLLY_HAVE_LIBSNAPPY(x) 1\n\
+             // #define FOLLY_HAVE_LIBLZMA 1\n",
+        );
+        assert!(header.is_set("FOLLY_HAVE_LIBGFLAGS"));
+        assert!(!header.is_set("FOLLY_HAVE_LIBURING"));
+        assert!(header.is_set("FOLLY_USE_JEMALLOC"));
+        assert!(header.is_set("FOLLY_HAVE_LIBAIO"));
+        assert!(!header.is_set("FOLLY_HAVE_LIBZSTD"));
+        assert!(!header.is_set("FOLLY_HAVE_LIBLZ4"));
+        assert!(!header.is_set("FOLLY_HAVE_LIBSNAPPY"));
+        assert!(!header.is_set("FOLLY_HAVE_LIBLZMA"));
+        assert!(!header.is_set("FOLLY_HAVE_LIBBZ2"));
+    }
+
+    #[test]
+    fn features_reflect_the_defines() {
+        let features = ConfigHeader::parse(
+            "#define FOLLY_HAVE_LIBGLOG 1\n\
+             #define FOLLY_HAVE_LIBURING 0\n\
+             #define FOLLY_HAS_COROUTINES 1\n",
+        )
+        .features();
+        assert!(features.glog);
+        assert!(!features.uring);
+        assert!(features.coroutines);
+        assert!(!features.gflags);
+    }
+
+    #[test]
+    fn version_strips_quotes() {
+        let header = ConfigHeader::parse("#define FOLLY_VERSION \"2023.05.22.00\"\n");
+        assert_eq!(header.version(), Some("2023.05.22.00"));
+        assert_eq!(ConfigHeader::parse("").version(), None);
+    }
+}
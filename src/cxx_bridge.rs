@@ -0,0 +1,37 @@
+// find-folly/src/cxx_bridge.rs
+//
+//! Building [`cxx`](https://cxx.rs) bridges that call into Folly. This module is only available
+//! with the `cxx` feature.
+
+use crate::Folly;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+
+impl Folly {
+    /// Returns a builder for the C++ side of the `#[cxx::bridge]` modules in `rust_source_files`,
+    /// as `cxx_build::bridges()` does, configured for Folly with [`Folly::apply_to()`].
+    ///
+    /// The bridge is linked against Folly, so this also prints the link plan with
+    /// [`Folly::emit_cargo_metadata()`], unless that was already called, for example by
+    /// [`crate::FollyProbe::probe()`].
+    ///
+    /// ```ignore
+    /// let folly = find_folly::probe_folly().unwrap();
+    /// folly
+    ///     .cxx_bridge(["src/lib.rs"])
+    ///     .file("src/executor.cpp")
+    ///     .compile("folly-bridge");
+    /// ```
+    pub fn cxx_bridge<I, P>(&self, rust_source_files: I) -> cc::Build
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut build = cxx_build::bridges(rust_source_files);
+        self.apply_to(&mut build);
+        if !self.emitted.load(Ordering::Relaxed) {
+            self.emit_cargo_metadata();
+        }
+        build
+    }
+}
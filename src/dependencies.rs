@@ -0,0 +1,242 @@
+// find-folly/src/dependencies.rs
+//
+//! Libraries that Folly depends on but that its `.pc` file doesn't mention.
+//!
+//! Which of these are needed depends on how Folly was configured, so the probe consults
+//! `folly/folly-config.h` before looking for them. Dependencies that turn out not to be needed, or
+//! that are optional and couldn't be found, are recorded as skipped rather than failing the probe.
+//!
+//! Besides `fmt`, `gflags`, and Boost, which need special handling, the libraries a static link
+//! needs are described by [`DEPENDENCIES`]. Each is looked up with `pkg-config` under each of its
+//! known package names, and failing that, by looking for the library file itself.
+
+use crate::config_header::ConfigHeader;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::path::PathBuf;
+
+/// A dependency of Folly that the probe didn't link against.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SkippedDependency {
+    /// The name of the dependency, such as `gflags`.
+    pub name: String,
+    /// Why the dependency was skipped.
+    pub reason: SkipReason,
+}
+
+/// Why a dependency was skipped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SkipReason {
+    /// The probe was configured not to look for it, for example with
+    /// [`crate::FollyProbe::require_gflags()`].
+    NotRequested,
+    /// `folly/folly-config.h` records that Folly was built without it.
+    NotUsed,
+    /// Folly was built against the header-only version, so there's nothing to link.
+    HeaderOnly,
+    /// The dependency is optional, Folly's configuration couldn't be determined, and the
+    /// dependency couldn't be found. The string describes the error.
+    NotFound(String),
+}
+
+impl Display for SkippedDependency {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "skipped `{}`: ", self.name)?;
+        match self.reason {
+            SkipReason::NotRequested => f.write_str("not requested"),
+            SkipReason::NotUsed => f.write_str("Folly was built without it"),
+            SkipReason::HeaderOnly => f.write_str("Folly uses the header-only library"),
+            SkipReason::NotFound(ref error) => write!(f, "not found ({})", error),
+        }
+    }
+}
+
+/// A library that a static link against Folly may need.
+pub(crate) struct Dependency {
+    /// The name reported when the dependency is missing or skipped.
+    pub(crate) name: &'static str,
+    /// The names of the `.pc` files that may describe it, most common first.
+    pub(crate) pc_names: &'static [&'static str],
+    /// The names it may be linked by, most common first. The `.pc` files of Folly and its
+    /// dependencies sometimes use names that don't exist on disk, so any of these is accepted.
+    pub(crate) lib_names: &'static [&'static str],
+    /// When the dependency is needed.
+    pub(crate) requirement: Requirement,
+}
+
+/// When a [`Dependency`] is needed.
+pub(crate) enum Requirement {
+    /// Folly always needs it.
+    Always,
+    /// Folly needs it if `folly-config.h` defines this macro. If the header can't be found, the
+    /// dependency is treated as optional.
+    IfConfigured(&'static str),
+    /// Folly may use it depending on what was found when it was built, and nothing records
+    /// whether it did. It's linked if it can be found.
+    Optional,
+}
+
+/// The libraries a static link against Folly may need, in link order: each library comes before
+/// the libraries it depends on, such as `libdwarf` before `zlib`.
+pub(crate) const DEPENDENCIES: &[Dependency] = &[
+    Dependency {
+        name: "glog",
+        pc_names: &["libglog"],
+        lib_names: &["glog"],
+        requirement: Requirement::IfConfigured("FOLLY_HAVE_LIBGLOG"),
+    },
+    Dependency {
+        name: "double-conversion",
+        pc_names: &["double-conversion"],
+        lib_names: &["double-conversion", "double_conversion"],
+        requirement: Requirement::Always,
+    },
+    Dependency {
+        name: "libevent",
+        pc_names: &["libevent", "libevent_core"],
+        lib_names: &["event", "event_core"],
+        requirement: Requirement::Always,
+    },
+    Dependency {
+        name: "libssl",
+        pc_names: &["libssl", "openssl"],
+        lib_names: &["ssl"],
+        requirement: Requirement::Always,
+    },
+    Dependency {
+        name: "libcrypto",
+        pc_names: &["libcrypto"],
+        lib_names: &["crypto"],
+        requirement: Requirement::Always,
+    },
+    Dependency {
+        name: "libsodium",
+        pc_names: &["libsodium"],
+        lib_names: &["sodium"],
+        requirement: Requirement::IfConfigured("FOLLY_HAVE_LIBSODIUM"),
+    },
+    Dependency {
+        name: "libunwind",
+        pc_names: &["libunwind"],
+        lib_names: &["unwind"],
+        requirement: Requirement::IfConfigured("FOLLY_HAVE_LIBUNWIND"),
+    },
+    Dependency {
+        name: "libdwarf",
+        pc_names: &["libdwarf"],
+        lib_names: &["dwarf"],
+        requirement: Requirement::IfConfigured("FOLLY_HAVE_DWARF"),
+    },
+    Dependency {
+        name: "libelf",
+        pc_names: &["libelf"],
+        lib_names: &["elf"],
+        requirement: Requirement::IfConfigured("FOLLY_HAVE_ELF"),
+    },
+    Dependency {
+        name: "libiberty",
+        pc_names: &[],
+        lib_names: &["iberty"],
+        requirement: Requirement::Optional,
+    },
+    Dependency {
+        name: "libaio",
+        pc_names: &["libaio"],
+        lib_names: &["aio"],
+        requirement: Requirement::IfConfigured("FOLLY_HAVE_LIBAIO"),
+    },
+    Dependency {
+        name: "liburing",
+        pc_names: &["liburing"],
+        lib_names: &["uring"],
+        requirement: Requirement::IfConfigured("FOLLY_HAVE_LIBURING"),
+    },
+    Dependency {
+        name: "zstd",
+        pc_names: &["libzstd"],
+        lib_names: &["zstd"],
+        requirement: Requirement::IfConfigured("FOLLY_HAVE_LIBZSTD"),
+    },
+    Dependency {
+        name: "lz4",
+        pc_names: &["liblz4"],
+        lib_names: &["lz4"],
+        requirement: Requirement::IfConfigured("FOLLY_HAVE_LIBLZ4"),
+    },
+    Dependency {
+        name: "snappy",
+        pc_names: &["snappy"],
+        lib_names: &["snappy"],
+        requirement: Requirement::IfConfigured("FOLLY_HAVE_LIBSNAPPY"),
+    },
+    Dependency {
+        name: "lzma",
+        pc_names: &["liblzma"],
+        lib_names: &["lzma"],
+        requirement: Requirement::IfConfigured("FOLLY_HAVE_LIBLZMA"),
+    },
+    Dependency {
+        name: "bz2",
+        pc_names: &["bzip2", "bz2"],
+        lib_names: &["bz2"],
+        requirement: Requirement::IfConfigured("FOLLY_HAVE_LIBBZ2"),
+    },
+    Dependency {
+        name: "zlib",
+        pc_names: &["zlib"],
+        lib_names: &["z"],
+        requirement: Requirement::IfConfigured("FOLLY_HAVE_LIBZ"),
+    },
+    Dependency {
+        name: "jemalloc",
+        pc_names: &["jemalloc"],
+        lib_names: &["jemalloc"],
+        requirement: Requirement::IfConfigured("FOLLY_USE_JEMALLOC"),
+    },
+];
+
+impl Dependency {
+    /// Returns `Some(true)` if Folly's configuration says the dependency is needed, `Some(false)`
+    /// if it says it isn't, and `None` if it's optional.
+    pub(crate) fn required(&self, config_header: Option<&ConfigHeader>) -> Option<bool> {
+        match self.requirement {
+            Requirement::Always => Some(true),
+            Requirement::IfConfigured(name) => {
+                config_header.map(|config_header| config_header.is_set(name))
+            }
+            Requirement::Optional => None,
+        }
+    }
+}
+
+/// Returns where the library linked as `name` belongs among the dependencies this module knows
+/// about, if it's one of them. Libraries with lower positions are linked first. `gflags`, which is
+/// probed separately rather than through [`DEPENDENCIES`], goes right after `glog`, which depends
+/// on it.
+pub(crate) fn link_position(name: &str) -> Option<usize> {
+    DEPENDENCIES
+        .iter()
+        .flat_map(|dependency| {
+            let depended_on: &[&str] = if dependency.name == "glog" {
+                &["gflags", "gflags_nothreads"]
+            } else {
+                &[]
+            };
+            dependency.lib_names.iter().chain(depended_on)
+        })
+        .position(|lib_name| *lib_name == name)
+}
+
+/// Finds a library that the linker would find with `-l<name>` in one of `dirs`, of the kind a link
+/// needs: static if `statik` is true, and shared otherwise. A `.lib` file may be either.
+pub(crate) fn find_library(dirs: &[PathBuf], name: &str, statik: bool) -> Option<PathBuf> {
+    let suffixes: &[&str] = if statik { &[".a"] } else { &[".so", ".dylib"] };
+    let file_names: Vec<_> = suffixes
+        .iter()
+        .map(|suffix| format!("lib{}{}", name, suffix))
+        .chain([format!("{}.lib", name)])
+        .collect();
+    dirs.iter()
+        .flat_map(|dir| file_names.iter().map(move |file_name| dir.join(file_name)))
+        .find(|path| path.is_file())
+}
@@ -0,0 +1,56 @@
+// find-folly/src/env_vars.rs
+//
+//! Environment variables that override how Folly is located, in the style of `openssl-sys`.
+//!
+//! Each variable can be suffixed with the target triple, with dashes replaced by underscores (for
+//! example `FOLLY_DIR_x86_64_unknown_linux_gnu`), in which case it takes precedence over the
+//! unsuffixed variable. This allows different installations to be used when cross-compiling.
+
+use std::env;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+/// The values of the `FOLLY_*` environment variables.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct EnvOverrides {
+    /// `FOLLY_DIR`: the installation prefix to search.
+    pub(crate) dir: Option<PathBuf>,
+    /// `FOLLY_INCLUDE_DIR`: the directory containing `folly/`.
+    pub(crate) include_dir: Option<PathBuf>,
+    /// `FOLLY_LIB_DIR`: the directory containing `libfolly`.
+    pub(crate) lib_dir: Option<PathBuf>,
+    /// `FOLLY_STATIC`: whether to link statically. Any value other than `0` means yes.
+    pub(crate) statik: Option<bool>,
+    /// The names of every variable consulted, including the target-suffixed ones, so that Cargo
+    /// can be told to rerun the build script when any of them change.
+    pub(crate) consulted: Vec<String>,
+}
+
+impl EnvOverrides {
+    pub(crate) fn read() -> Self {
+        let mut overrides = EnvOverrides::default();
+        overrides.dir = overrides.var("FOLLY_DIR").map(PathBuf::from);
+        overrides.include_dir = overrides.var("FOLLY_INCLUDE_DIR").map(PathBuf::from);
+        overrides.lib_dir = overrides.var("FOLLY_LIB_DIR").map(PathBuf::from);
+        overrides.statik = overrides.var("FOLLY_STATIC").map(|value| value != "0");
+        overrides
+    }
+
+    // Reads `NAME_<target>`, falling back to `NAME`.
+    fn var(&mut self, name: &str) -> Option<OsString> {
+        let mut names = vec![];
+        if let Ok(target) = env::var("TARGET") {
+            names.push(format!("{}_{}", name, target.replace('-', "_")));
+        }
+        names.push(name.to_owned());
+
+        let mut value = None;
+        for name in names {
+            if value.is_none() {
+                value = env::var_os(&name);
+            }
+            self.consulted.push(name);
+        }
+        value
+    }
+}
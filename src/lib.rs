@@ -2,160 +2,1079 @@
 //
 //! This crate is a simple build dependency you can use in your `build.rs` scripts to compile and
 //! link against the [Folly C++ library](https://github.com/facebook/folly).
-//! 
+//!
 //! In theory, the [`pkg-config`](https://crates.io/crates/pkg-config) library would be all you
 //! need in order to locate Folly, because Folly is typically packed with a `.pc` file. In
 //! practice, that is insufficient, because the `.pc` file doesn't fully describe all the
 //! dependencies that Folly has, and it has bugs. This crate knows about these idiosyncrasies and
 //! provides workarounds for them.
-//! 
-//! The following snippet should suffice for most use cases:
+//!
+//! Folly is located with `pkg-config` when possible. If `libfolly.pc` can't be found, this crate
+//! falls back to the CMake package configuration that Folly installs (`folly-config.cmake`), which
+//! describes Folly's dependencies more completely. If no `pkg-config` binary is installed at all,
+//! `libfolly.pc` is read directly.
+//!
+//! With the `cc` feature enabled, the following snippet should suffice for most use cases:
 //!
 //! ```ignore
 //! let folly = find_folly::probe_folly().unwrap();
 //! let mut build = cc::Build::new();
 //! ... populate `build` ...
-//! build.includes(&folly.include_paths);
-//! for other_cflag in &folly.other_cflags {
-//!     build.flag(other_cflag);
+//! folly.apply_to(&mut build);
+//! ```
+//!
+//! `apply_to()` sets Folly's include paths, defines, and C++ standard. Without the feature, the same
+//! information is in the fields of [`Folly`].
+//!
+//! The `cxx` feature does the same for [`cxx`](https://cxx.rs) bridges that call into Folly:
+//!
+//! ```ignore
+//! folly.cxx_bridge(["src/lib.rs"]).file("src/shim.cpp").compile("folly-bridge");
+//! ```
+//!
+//! To generate bindings, pass `folly.clang_args()` to `bindgen`, or enable the `bindgen` feature and
+//! start from `folly.bindgen_builder()`.
+//!
+//! CMake projects built with the `cmake` crate that call `find_package(folly)` can be pointed at the
+//! same installation with `folly.configure_cmake(&mut config)`, which requires the `cmake` feature.
+//!
+//! If you need more control over how Folly is located, use [`FollyProbe`]:
+//!
+//! ```ignore
+//! let folly = find_folly::FollyProbe::new()
+//!     .atleast_version("2022.08.29.00")
+//!     .search_path("/opt/boost/lib")
+//!     .require_gflags(false)
+//!     .probe()
+//!     .unwrap();
+//! ```
+//!
+//! Probing doesn't have to print anything. Turn off `cargo_metadata` to inspect or adjust the
+//! link directives before handing them to Cargo:
+//!
+//! ```ignore
+//! let mut folly = find_folly::FollyProbe::new().cargo_metadata(false).probe().unwrap();
+//! folly.link_directives.retain(|directive| ...);
+//! folly.emit_cargo_metadata();
+//! ```
+//!
+//! The optional features Folly was built with are parsed from `folly/folly-config.h`, so build
+//! scripts can branch on what the installation supports:
+//!
+//! ```ignore
+//! if folly.features.as_ref().is_some_and(|features| features.uring) {
+//!     build.file("src/uring_shim.cpp");
 //! }
 //! ```
+//!
+//! Folly relies on static initializers for singletons and `FOLLY_INIT` hooks, which an ordinary
+//! static link discards. Link `libfolly.a` with `+whole-archive` to keep them:
+//!
+//! ```ignore
+//! let folly = find_folly::FollyProbe::new().whole_archive("folly").probe().unwrap();
+//! ```
+//!
+//! To use a Folly installed under a particular prefix, call [`probe_folly_at()`] or set the
+//! `FOLLY_DIR` environment variable. `FOLLY_INCLUDE_DIR` and `FOLLY_LIB_DIR` name the header and
+//! library directories directly, and `FOLLY_STATIC=0` selects dynamic linking. See
+//! [`FollyProbe::probe()`] for details.
+//!
+//! When Folly lives in a sysroot, such as a macOS SDK or a cross-compilation root, the sysroot is
+//! taken from Folly's `--sysroot` or `-isysroot` flag, `PKG_CONFIG_SYSROOT_DIR`, or `SDKROOT`. It's
+//! recorded in [`Folly::sysroot`], and the flag that selects it is passed to the compiler and
+//! linker.
 
-use pkg_config::{Config, Error as PkgConfigError};
-use shlex::Shlex;
+use std::env;
+use std::ffi::OsString;
 use std::io::Error as IoError;
+use std::ops::{Bound, RangeBounds};
 use std::path::{Path, PathBuf};
-use std::process::Command;
-use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use thiserror::Error;
 
+use crate::boost::BoostResolver;
+use crate::cflags::Cflag;
+use crate::cmake_package::CMakePackage;
+use crate::config_header::ConfigHeader;
+use crate::dependencies::{Dependency, Requirement};
+use crate::env_vars::EnvOverrides;
+use crate::link::LibraryPath;
+use crate::query::{PkgConfig, Query};
+
+pub use crate::boost::BoostLibrary;
+pub use crate::config_header::FollyFeatures;
+pub use crate::dependencies::{SkipReason, SkippedDependency};
+pub use crate::link::{LinkDirective, LinkKind, LinkLib, LinkModifier, SearchKind, SearchPath};
+pub use crate::version::FollyVersion;
+
+#[cfg(feature = "bindgen")]
+mod bindgen_builder;
+mod boost;
+#[cfg(feature = "cc")]
+mod cc_build;
+mod cflags;
+#[cfg(feature = "cmake")]
+mod cmake_config;
+mod cmake_package;
+mod config_header;
+#[cfg(feature = "cxx")]
+mod cxx_bridge;
+mod dependencies;
+mod env_vars;
+mod link;
+mod pc_file;
+mod query;
+mod sysroot;
+mod version;
+
 /// Information about the Folly library.
 ///
 /// You can the information in this structure to populate a `cc::Build` in order to compile code
 /// that uses Folly:
 ///
-///     let folly = find_folly::probe_folly().unwrap();
-///     let mut build = cc::Build::new();
-///     ... populate `build` ...
-///     build.includes(&folly.include_paths);
-///     for other_cflag in &folly.other_cflags {
-///         build.flag(other_cflag);
-///     }
+/// ```ignore
+/// let folly = find_folly::probe_folly().unwrap();
+/// let mut build = cc::Build::new();
+/// ... populate `build` ...
+/// build.includes(&folly.include_paths);
+/// for (name, value) in &folly.defines {
+///     build.define(name, value.as_deref());
+/// }
+/// if let Some(ref cpp_std) = folly.cpp_std {
+///     build.std(cpp_std);
+/// }
+/// for other_cflag in &folly.other_cflags {
+///     build.flag(other_cflag);
+/// }
+/// ```
+#[non_exhaustive]
 pub struct Folly {
     pub lib_dirs: Vec<PathBuf>,
+    /// Directories passed with `-I`.
     pub include_paths: Vec<PathBuf>,
+    /// Directories passed with `-isystem`.
+    pub system_include_paths: Vec<PathBuf>,
+    /// Preprocessor definitions passed with `-D`, as `(name, value)` pairs.
+    pub defines: Vec<(String, Option<String>)>,
+    /// The C++ language standard Folly was built with, from `-std=`, for example `c++17`.
+    pub cpp_std: Option<String>,
+    /// All other compiler flags, in order. Flags that take a separate argument, such as
+    /// `-isysroot`, are followed by that argument.
     pub other_cflags: Vec<String>,
-    _priv: (),
+    /// Everything the linker needs in order to link against Folly and its dependencies, in
+    /// order: search paths first, then Folly's libraries, then its dependencies, each listed once
+    /// and after everything that needs it, with the dependencies this crate knows about, such as
+    /// `glog` and `gflags`, in an order that links, then any other linker flags. Call
+    /// [`Folly::emit_cargo_metadata()`] to pass these on to Cargo.
+    pub link_directives: Vec<LinkDirective>,
+    /// The directory containing `folly-config.cmake`, if Folly was located through its CMake
+    /// package configuration.
+    pub cmake_dir: Option<PathBuf>,
+    /// The installed version of Folly, from `FOLLY_VERSION` in `folly/folly-config.h` or else from
+    /// `pkg-config --modversion` or `folly-config-version.cmake`. `None` if none of those record
+    /// it.
+    pub version: Option<FollyVersion>,
+    /// The location of `folly/folly-config.h`, if it was found in the include paths.
+    pub config_header: Option<PathBuf>,
+    /// The optional features Folly was built with, parsed from `folly/folly-config.h`. `None` if
+    /// the header wasn't found.
+    pub features: Option<FollyFeatures>,
+    /// The Boost libraries that were chosen, one per component.
+    pub boost_libraries: Vec<BoostLibrary>,
+    /// Dependencies that weren't linked against, and why.
+    pub skipped_dependencies: Vec<SkippedDependency>,
+    /// Problems that didn't stop the probe but may break the build.
+    /// [`Folly::emit_cargo_metadata()`] prints each as a `cargo:warning`.
+    pub warnings: Vec<String>,
+    /// Files that the probe read or chose, such as `.pc` files, `folly/folly-config.h`, and the
+    /// `boost_context` library. [`Folly::emit_cargo_metadata()`] emits a `cargo:rerun-if-changed`
+    /// line for each.
+    pub rerun_if_changed: Vec<PathBuf>,
+    /// Environment variables that affected the probe. [`Folly::emit_cargo_metadata()`] emits a
+    /// `cargo:rerun-if-env-changed` line for each.
+    pub rerun_if_env_changed: Vec<String>,
+    /// The sysroot that Folly was found in, such as a macOS SDK or a cross-compilation root. It
+    /// comes from a `--sysroot` or `-isysroot` flag in Folly's compiler flags, an SDK's
+    /// `usr/include` in its include paths, `PKG_CONFIG_SYSROOT_DIR`, or, when building for an
+    /// Apple platform, `SDKROOT`. The flag that selects it is in [`Folly::other_cflags`], and for
+    /// `--sysroot`, in [`Folly::link_directives`] as well.
+    pub sysroot: Option<PathBuf>,
+    // Whether `emit_cargo_metadata()` has been called. Printing the same modifiers twice is an
+    // error, so helpers that emit the metadata themselves check this first.
+    emitted: AtomicBool,
+}
+
+/// A builder that configures how Folly is located.
+///
+/// This is modelled on `pkg_config::Config`. The defaults match the behavior of
+/// [`probe_folly()`], which is simply shorthand for `FollyProbe::new().probe()`.
+#[derive(Clone, Debug)]
+pub struct FollyProbe {
+    method: DiscoveryMethod,
+    prefix: Option<PathBuf>,
+    statik: bool,
+    search_paths: Vec<PathBuf>,
+    min_version: Bound<String>,
+    max_version: Bound<String>,
+    require_fmt: bool,
+    require_gflags: bool,
+    require_boost_context: bool,
+    require_dependencies: bool,
+    whole_archive: Vec<String>,
+    bundle: Option<bool>,
+    link_group: bool,
+    cargo_metadata: bool,
+}
+
+/// How [`FollyProbe`] locates Folly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiscoveryMethod {
+    /// Try `pkg-config` first, and fall back to CMake if `libfolly.pc` can't be found. Failing to
+    /// locate one of Folly's dependencies doesn't cause a fallback, since the CMake package may
+    /// belong to a different installation. This is the default.
+    Auto,
+    /// Use only `libfolly.pc`, via `pkg-config` or by reading the file directly.
+    PkgConfig,
+    /// Use only Folly's CMake package configuration (`folly-config.cmake` and
+    /// `folly-targets.cmake`). The search honors `folly_DIR` and `CMAKE_PREFIX_PATH`.
+    ///
+    /// The exported `Folly::folly` target lists Folly's dependencies, so `fmt` and `gflags` are
+    /// not probed separately in this mode.
+    CMake,
 }
 
 #[derive(Error, Debug)]
 pub enum FollyError {
     #[error("`fmt` dependency couldn't be located")]
-    FmtDependency(PkgConfigError),
+    FmtDependency(#[source] Box<FollyError>),
     #[error("`gflags` dependency couldn't be located")]
-    GflagsDependency(PkgConfigError),
+    GflagsDependency(#[source] Box<FollyError>),
     #[error("main `folly` package couldn't be located")]
-    MainPackage(IoError),
-    #[error("could not find `boost_context`; make sure either `libboost_context.a` or \
-            `libboost_context-mt.a` is located in the same directory as Folly")]
+    MainPackage(#[source] Box<FollyError>),
+    /// The `pkg-config` binary exists but couldn't be run.
+    #[error("`pkg-config` couldn't be run")]
+    PkgConfigSpawn(#[source] IoError),
+    /// `pkg-config` ran but reported an error, typically because the `.pc` file couldn't be found.
+    #[error(
+        "`pkg-config` failed for `{package}` ({}; `PKG_CONFIG_PATH` {}): {stderr}",
+        describe_exit_code(*.code),
+        describe_env_var(.pkg_config_path.as_ref())
+    )]
+    PkgConfig {
+        package: String,
+        /// The exit code of `pkg-config`, or `None` if it was killed by a signal.
+        code: Option<i32>,
+        /// Whatever `pkg-config` printed to standard error.
+        stderr: String,
+        /// The value of `PKG_CONFIG_PATH` when `pkg-config` was run, if set.
+        pkg_config_path: Option<OsString>,
+    },
+    /// No `pkg-config` binary was found, and the `.pc` file for the package wasn't in any of the
+    /// directories `pkg-config` would have searched.
+    #[error("couldn't find `{package}.pc` in any of {search_dirs:?}")]
+    PcFileNotFound {
+        package: String,
+        search_dirs: Vec<PathBuf>,
+    },
+    /// A `.pc` file or CMake package configuration file couldn't be read.
+    #[error("couldn't read `{}`", .0.display())]
+    ReadFile(PathBuf, #[source] IoError),
+    /// Folly's CMake package configuration wasn't in any of the directories searched.
+    #[error("couldn't find `folly-config.cmake` in any of {search_dirs:?}")]
+    CMakePackageNotFound { search_dirs: Vec<PathBuf> },
+    /// Folly's CMake package configuration doesn't define the `Folly::folly` target.
+    #[error("`{}` doesn't define the `Folly::folly` target", .0.display())]
+    CMakeTarget(PathBuf),
+    /// Libraries that Folly's configuration calls for couldn't be found, either with `pkg-config`
+    /// or in the library search paths.
+    #[error(
+        "couldn't locate Folly's dependencies {}; install them or pass their directories to \
+            `FollyProbe::search_path()`",
+        describe_names(.0)
+    )]
+    MissingDependencies(Vec<String>),
+    /// A version string couldn't be parsed.
+    #[error("`{0}` isn't a valid Folly version")]
+    InvalidVersion(String),
+    /// The installed version of Folly doesn't satisfy the probe's version requirement.
+    #[error(
+        "found Folly version {}, but version {required} is required",
+        .found.as_ref().map_or("unknown".to_owned(), ToString::to_string)
+    )]
+    Version {
+        /// The installed version, or `None` if it couldn't be determined.
+        found: Option<FollyVersion>,
+        /// The requirement, such as `>= 2022.08.29`.
+        required: String,
+    },
+    /// `folly/folly-config.h` records a different version than `pkg-config` or CMake reported,
+    /// which means the headers and libraries come from different installations.
+    #[error(
+        "Folly version {reported} was located, but `{}` is from version {header}",
+        .header_path.display()
+    )]
+    VersionMismatch {
+        reported: FollyVersion,
+        header: FollyVersion,
+        header_path: PathBuf,
+    },
+    /// A library is only available as a shared library when linking statically, or the other way
+    /// around.
+    #[error(
+        "a {} `{name}` library is required, but only `{}` was found",
+        if *.statik { "static" } else { "shared" },
+        .found.display()
+    )]
+    LinkKindUnavailable {
+        name: String,
+        /// Whether a static library was required.
+        statik: bool,
+        /// The library of the other kind that was found.
+        found: PathBuf,
+    },
+    #[error(
+        "could not find `boost_context`; make sure `libboost_context.a`, possibly with tags such \
+            as `libboost_context-mt.a`, is located in the same directory as Folly, in one of the \
+            probe's search paths, or under `BOOST_ROOT` or `BOOST_LIBRARYDIR`"
+    )]
     BoostContext,
 }
 
+/// Locates Folly using the default configuration.
+///
+/// This is equivalent to `FollyProbe::new().probe()`.
 pub fn probe_folly() -> Result<Folly, FollyError> {
-    // Folly's `.pc` file is missing the `fmt` and `gflags` dependencies. Find them here.
-    Config::new()
-        .statik(true)
-        .probe("fmt")
-        .map_err(FollyError::FmtDependency)?;
-    Config::new()
-        .statik(true)
-        .probe("gflags")
-        .map_err(FollyError::GflagsDependency)?;
-
-    // Unfortunately, the `pkg-config` crate doesn't successfully parse some of Folly's
-    // dependencies, because it passes the raw `.so` files instead of using `-l` flags. So call
-    // `pkg-config` manually.
-    let mut folly = Folly::new();
-    let output = Command::new("pkg-config")
-        .args(&["--static", "--libs", "libfolly"])
-        .output()
-        .map_err(FollyError::MainPackage)?;
-    let output = String::from_utf8(output.stdout).expect("`pkg-config --libs` wasn't UTF-8!");
-    for arg in Shlex::new(&output) {
-        if arg.starts_with('-') {
-            if let Some(rest) = arg.strip_prefix("-L") {
-                folly.lib_dirs.push(PathBuf::from(rest));
-            } else if let Some(rest) = arg.strip_prefix("-l") {
-                println!("cargo:rustc-link-lib={}", rest);
-            }
-            continue;
-        }
-
-        let path = PathBuf::from_str(&arg).unwrap();
-        let (parent, lib_name) = match (path.parent(), path.file_stem()) {
-            (Some(parent), Some(lib_name)) => (parent, lib_name),
-            _ => continue,
+    FollyProbe::new().probe()
+}
+
+/// Locates the Folly installed under `prefix`, using the default configuration otherwise.
+///
+/// This is equivalent to `FollyProbe::new().prefix(prefix).probe()`.
+pub fn probe_folly_at<P: Into<PathBuf>>(prefix: P) -> Result<Folly, FollyError> {
+    FollyProbe::new().prefix(prefix).probe()
+}
+
+impl FollyProbe {
+    /// Creates a new probe with the default configuration: Folly is linked statically, all
+    /// dependencies are required, and `cargo:` directives are printed to standard output.
+    pub fn new() -> Self {
+        Self {
+            method: DiscoveryMethod::Auto,
+            prefix: None,
+            statik: true,
+            search_paths: vec![],
+            min_version: Bound::Unbounded,
+            max_version: Bound::Unbounded,
+            require_fmt: true,
+            require_gflags: true,
+            require_boost_context: true,
+            require_dependencies: true,
+            whole_archive: vec![],
+            bundle: None,
+            link_group: false,
+            cargo_metadata: true,
+        }
+    }
+
+    /// Selects how Folly is located. Defaults to [`DiscoveryMethod::Auto`].
+    pub fn method(&mut self, method: DiscoveryMethod) -> &mut Self {
+        self.method = method;
+        self
+    }
+
+    /// Looks for Folly under the installation prefix `prefix` only.
+    ///
+    /// The prefix is searched for `libfolly.pc` and then for `folly-config.cmake`. If neither is
+    /// present, Folly is assumed to be in `<prefix>/include` and `<prefix>/lib`. The prefix's
+    /// `pkgconfig` directories are also searched first for `fmt` and `gflags`.
+    ///
+    /// The `FOLLY_DIR` environment variable overrides this.
+    pub fn prefix<P: Into<PathBuf>>(&mut self, prefix: P) -> &mut Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Indicates whether Folly and its dependencies should be linked statically. Defaults to true.
+    ///
+    /// In static mode, `--static` is passed to `pkg-config` so that the `Libs.private` closure is
+    /// included, and `libboost_context.a` must be found. In dynamic mode, only `Libs` is used,
+    /// libraries are linked as `dylib`, and `boost_context` is linked only if a shared copy is
+    /// found, since a shared `libfolly.so` already records its own dependency on it.
+    ///
+    /// Either way, each library found in the search paths is linked with an explicit `static` or
+    /// `dylib` kind, so the linker can't substitute the other kind, and the probe fails if only the
+    /// other kind is installed. System libraries such as `libdl` are left to the linker.
+    ///
+    /// The `FOLLY_STATIC` environment variable overrides this.
+    pub fn statik(&mut self, statik: bool) -> &mut Self {
+        self.statik = statik;
+        self
+    }
+
+    /// Adds a directory to search for libraries that Folly's `.pc` file omits, such as
+    /// `boost_context`.
+    ///
+    /// The directory is also passed to the linker as a library search path. Folly's own library
+    /// directories are always searched first.
+    pub fn search_path<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        self.search_paths.push(path.into());
+        self
+    }
+
+    /// Requires Folly to be at least version `version`, such as `2022.08.29.00`.
+    pub fn atleast_version(&mut self, version: &str) -> &mut Self {
+        self.range_version(version..)
+    }
+
+    /// Requires Folly to be exactly version `version`. Trailing zero components are ignored, so
+    /// `2022.08.29` matches `2022.08.29.00`.
+    pub fn exactly_version(&mut self, version: &str) -> &mut Self {
+        self.range_version(version..=version)
+    }
+
+    /// Requires the version of Folly to be in `range`, for example
+    /// `"2022.08.29".."2024.01.01"`.
+    ///
+    /// If a version requirement is set and the version can't be determined, the probe fails.
+    pub fn range_version<'a, R>(&mut self, range: R) -> &mut Self
+    where
+        R: RangeBounds<&'a str>,
+    {
+        self.min_version = range.start_bound().map(|version| (*version).to_owned());
+        self.max_version = range.end_bound().map(|version| (*version).to_owned());
+        self
+    }
+
+    /// Indicates whether the `fmt` dependency must be located. Defaults to true.
+    ///
+    /// Even when true, `fmt` isn't linked if Folly was built against the header-only version of
+    /// it (`FMT_HEADER_ONLY`).
+    pub fn require_fmt(&mut self, require: bool) -> &mut Self {
+        self.require_fmt = require;
+        self
+    }
+
+    /// Indicates whether the `gflags` dependency should be located. Defaults to true.
+    ///
+    /// Even when true, `gflags` is only required if `folly/folly-config.h` records that Folly was
+    /// built with it. If the header can't be found, `gflags` is linked if it can be found, and
+    /// skipped otherwise.
+    pub fn require_gflags(&mut self, require: bool) -> &mut Self {
+        self.require_gflags = require;
+        self
+    }
+
+    /// Indicates whether `boost_context` must be located when linking statically. Defaults to
+    /// true.
+    pub fn require_boost_context(&mut self, require: bool) -> &mut Self {
+        self.require_boost_context = require;
+        self
+    }
+
+    /// Indicates whether the other libraries that a static Folly needs, such as `glog`,
+    /// `double-conversion`, and `libevent`, must be located. Defaults to true.
+    ///
+    /// Only the libraries that Folly's configuration calls for are required. If this is false,
+    /// missing libraries are recorded in [`Folly::skipped_dependencies`] instead of failing the
+    /// probe.
+    pub fn require_dependencies(&mut self, require: bool) -> &mut Self {
+        self.require_dependencies = require;
+        self
+    }
+
+    /// Links the static library `name`, as it's named to the linker (such as `folly` or `gflags`),
+    /// with the `+whole-archive` modifier, so that all of its objects are kept. May be called more
+    /// than once.
+    ///
+    /// Folly registers singletons, `FOLLY_INIT` hooks, and gflags flags in static initializers.
+    /// Nothing refers to the objects that contain them, so an ordinary static link drops them and
+    /// the features silently go missing at runtime. This has no effect when linking dynamically.
+    pub fn whole_archive<S: Into<String>>(&mut self, name: S) -> &mut Self {
+        self.whole_archive.push(name.into());
+        self
+    }
+
+    /// Sets the `bundle` modifier on the static libraries that are linked. By default, rustc
+    /// decides, and bundles them into the `.rlib` of the crate whose build script links them.
+    ///
+    /// With `bundle(false)`, the libraries are only linked into the final binary, which saves
+    /// copying a large `libfolly.a` into every `.rlib` that depends on it.
+    pub fn bundle(&mut self, bundle: bool) -> &mut Self {
+        self.bundle = Some(bundle);
+        self
+    }
+
+    /// Indicates whether the static libraries should be wrapped in `--start-group` and
+    /// `--end-group`, so that the linker resolves references among them regardless of order.
+    /// Defaults to false.
+    ///
+    /// Cargo can't group `cargo:rustc-link-lib` directives, so this passes the libraries with
+    /// `cargo:rustc-link-arg` instead. That only works with GNU-style linkers, and only for the
+    /// package's own binaries, tests, and examples.
+    pub fn link_group(&mut self, link_group: bool) -> &mut Self {
+        self.link_group = link_group;
+        self
+    }
+
+    /// Indicates whether [`FollyProbe::probe()`] should call [`Folly::emit_cargo_metadata()`] on
+    /// success. Defaults to true.
+    ///
+    /// Turn this off to probe without side effects; no `cargo:` directives are printed until you
+    /// call `emit_cargo_metadata()` yourself.
+    pub fn cargo_metadata(&mut self, cargo_metadata: bool) -> &mut Self {
+        self.cargo_metadata = cargo_metadata;
+        self
+    }
+
+    /// Locates Folly using this configuration.
+    ///
+    /// The following environment variables override the configuration, and may be suffixed with
+    /// the target triple (for example `FOLLY_DIR_x86_64_unknown_linux_gnu`) to apply to only that
+    /// target:
+    ///
+    /// * `FOLLY_DIR`: the installation prefix, as with [`FollyProbe::prefix()`].
+    /// * `FOLLY_INCLUDE_DIR` and `FOLLY_LIB_DIR`: the directories containing Folly's headers and
+    ///   libraries. Setting either skips discovery entirely.
+    /// * `FOLLY_STATIC`: whether to link statically; `0` means no and anything else means yes.
+    pub fn probe(&self) -> Result<Folly, FollyError> {
+        // Environment variables take precedence over the builder, so that whoever runs the build
+        // can redirect it without editing `build.rs`.
+        let overrides = EnvOverrides::read();
+        let mut probe = self.clone();
+        if let Some(statik) = overrides.statik {
+            probe.statik = statik;
+        }
+        if let Some(ref dir) = overrides.dir {
+            probe.prefix = Some(dir.clone());
+        }
+        probe.probe_with_overrides(&overrides)
+    }
+
+    fn probe_with_overrides(&self, overrides: &EnvOverrides) -> Result<Folly, FollyError> {
+        let mut folly = if overrides.lib_dir.is_some() || overrides.include_dir.is_some() {
+            let lib_dir = overrides
+                .lib_dir
+                .clone()
+                .or_else(|| self.prefix.as_ref().map(|prefix| prefix.join("lib")));
+            let include_dir = overrides
+                .include_dir
+                .clone()
+                .or_else(|| self.prefix.as_ref().map(|prefix| prefix.join("include")))
+                .or_else(|| lib_dir.as_ref().map(|lib_dir| lib_dir.join("../include")));
+            self.probe_explicit(lib_dir, include_dir, vec![])?
+        } else if let Some(ref prefix) = self.prefix {
+            self.probe_prefix(prefix)?
+        } else {
+            match self.method {
+                DiscoveryMethod::PkgConfig => self.probe_pkg_config(vec![])?,
+                DiscoveryMethod::CMake => self.probe_cmake()?,
+                DiscoveryMethod::Auto => match self.probe_pkg_config(vec![]) {
+                    Ok(folly) => folly,
+                    // If CMake doesn't work either, the `pkg-config` error is the more useful one
+                    // to report.
+                    Err(error @ FollyError::MainPackage(_)) => {
+                        let mut folly = self.probe_cmake().map_err(|_| error)?;
+                        // Fixing the `pkg-config` setup would change the outcome.
+                        for var in query::ENV_VARS {
+                            folly.track_env_var(var);
+                        }
+                        folly
+                    }
+                    Err(error) => return Err(error),
+                },
+            }
         };
-        let lib_name = lib_name.to_string_lossy();
-        if let Some(rest) = lib_name.strip_prefix("lib") {
-            println!("cargo:rustc-link-search={}", parent.display());
-            println!("cargo:rustc-link-lib={}", rest);
+        for var in &overrides.consulted {
+            folly.track_env_var(var);
         }
+        folly.detect_sysroot();
+        if let Some(config_header_path) = folly.find_config_header() {
+            folly.track_file(&config_header_path);
+            let config_header = ConfigHeader::read(&config_header_path)?;
+            let mut features = config_header.features();
+            features.coroutines |= folly.coroutines_enabled();
+            folly.features = Some(features);
+            // A version that isn't purely numeric, such as `2023.01.02.00-dev`, is treated like a
+            // missing one: there's nothing to cross-check, and only a version requirement fails.
+            if let Some(header_version) = config_header
+                .version()
+                .and_then(|version| version.parse::<FollyVersion>().ok())
+            {
+                // A mismatch means the headers belong to a different installation than the
+                // libraries, which will fail in confusing ways at link time.
+                if let Some(reported) = folly.version.take() {
+                    if reported != header_version {
+                        return Err(FollyError::VersionMismatch {
+                            reported,
+                            header: header_version,
+                            header_path: config_header_path,
+                        });
+                    }
+                }
+                folly.version = Some(header_version);
+            }
+            folly.config_header = Some(config_header_path);
+        }
+        version::check(folly.version.as_ref(), &self.min_version, &self.max_version)?;
+
+        if self.statik {
+            self.resolve_dependencies(&mut folly)?;
+        }
+        for lib_dir in folly.lib_dirs.iter().chain(self.search_paths.iter()) {
+            folly
+                .link_directives
+                .push(LinkDirective::SearchPath(SearchPath::native(lib_dir)));
+        }
+        self.resolve_boost(&mut folly)?;
+        let mut lib_dirs: Vec<PathBuf> = folly
+            .link_directives
+            .iter()
+            .filter_map(|directive| match *directive {
+                LinkDirective::SearchPath(ref search_path)
+                    if search_path.kind == SearchKind::Native =>
+                {
+                    Some(search_path.path.clone())
+                }
+                _ => None,
+            })
+            .collect();
+        lib_dirs.extend(link::system_lib_dirs(folly.sysroot.as_deref()));
+        link::resolve_kinds(&mut folly.link_directives, &lib_dirs, self.statik)?;
+        let unmatched = link::apply_modifiers(
+            &mut folly.link_directives,
+            &self.whole_archive,
+            self.bundle,
+            self.statik,
+        );
+        if self.statik {
+            for name in unmatched {
+                folly.warnings.push(format!(
+                    "`{}` was passed to `whole_archive()` but isn't linked statically",
+                    name
+                ));
+            }
+        }
+        folly.link_directives = link::consolidate(folly.link_directives, self.link_group);
+
+        if self.cargo_metadata {
+            folly.emit_cargo_metadata();
+        }
+        Ok(folly)
     }
 
-    // Unfortunately, just like `fmt` and `gflags`, Folly's `.pc` file doesn't contain a link flag
-    // for `boost_context`. What's worse, the name varies based on different systems
-    // (`libboost_context.a` vs.  `libboost_context-mt.a`). So find that library manually. We assume
-    // it's in the same directory as the Folly installation itself.
-    let mut found_boost_context = false;
-    for lib_dir in &folly.lib_dirs {
-        println!("cargo:rustc-link-search={}", lib_dir.display());
+    // Locates Folly using `libfolly.pc`, searching `pc_dirs` before the usual places.
+    fn probe_pkg_config(&self, pc_dirs: Vec<PathBuf>) -> Result<Folly, FollyError> {
+        let mut folly = Folly::new();
+        let pkg_config = PkgConfig::new(self.statik).search_dirs_first(pc_dirs);
+        for path in pkg_config.pc_files("libfolly") {
+            folly.track_file(&path);
+        }
+        let libs = pkg_config
+            .query("libfolly", Query::Libs)
+            .map_err(|error| FollyError::MainPackage(Box::new(error)))?;
+        folly.add_libs(libs, self.link_kind());
 
-        if found_boost_context {
-            continue;
+        let cflags = pkg_config
+            .query("libfolly", Query::Cflags)
+            .map_err(|error| FollyError::MainPackage(Box::new(error)))?;
+        for cflag in cflags::classify_cflags(cflags) {
+            folly.add_cflag(cflag);
         }
-        for possible_lib_name in &["boost_context", "boost_context-mt"] {
-            let mut lib_dir = (*lib_dir).clone();
-            lib_dir.push(&format!("lib{}.a", possible_lib_name));
-            if !lib_dir.exists() {
+
+        folly.version = pkg_config
+            .modversion("libfolly")
+            .ok()
+            .and_then(|version| version.parse().ok());
+
+        // Which dependencies are needed depends on the sysroot's `folly-config.h`, not the host's.
+        folly.detect_sysroot();
+        // The dependencies come after Folly, so that static linking resolves Folly's references
+        // to them.
+        self.probe_pkg_config_dependencies(&pkg_config, &mut folly)?;
+        Ok(folly)
+    }
+
+    // A static `libfolly.a` leaves all of its dependencies to the final link, and the `.pc` file
+    // often omits or misnames some of them. Make sure that each one Folly's configuration calls for
+    // is linked, and report the ones that can't be found by name.
+    fn resolve_dependencies(&self, folly: &mut Folly) -> Result<(), FollyError> {
+        let config_header = match folly.config_header {
+            Some(ref path) => Some(ConfigHeader::read(path)?),
+            None => None,
+        };
+        let mut lib_dirs = folly.lib_dirs.clone();
+        lib_dirs.extend(self.search_paths.iter().cloned());
+        let system_dirs = link::system_lib_dirs(folly.sysroot.as_deref());
+        lib_dirs.extend(system_dirs.iter().cloned());
+
+        let pkg_config = PkgConfig::new(self.statik);
+        let mut missing = vec![];
+        for dependency in dependencies::DEPENDENCIES {
+            let required = dependency.required(config_header.as_ref());
+            if required == Some(false) {
+                folly.skip_dependency(dependency.name, SkipReason::NotUsed);
                 continue;
             }
-            println!("cargo:rustc-link-lib={}", possible_lib_name);
-            found_boost_context = true;
-            break;
+            if folly.fix_dependency_name(dependency, &lib_dirs, self.statik) {
+                continue;
+            }
+
+            let pc_libs = dependency.pc_names.iter().find_map(|pc_name| {
+                let libs = pkg_config.query(pc_name, Query::Libs).ok()?;
+                Some((pc_name, libs))
+            });
+            if let Some((pc_name, libs)) = pc_libs {
+                for path in pkg_config.pc_files(pc_name) {
+                    folly.track_file(&path);
+                }
+                let first_added = folly.link_directives.len();
+                folly.add_libs(libs, self.link_kind());
+                // A dependency that may not be needed is skipped if only the wrong kind of
+                // library is installed, rather than failing the probe.
+                if required != Some(true) {
+                    // The dependency's own `-L` directories are in `folly.lib_dirs` now.
+                    let dirs: Vec<_> = folly.lib_dirs.iter().chain(&lib_dirs).cloned().collect();
+                    let added = &mut folly.link_directives[first_added..];
+                    if let Err(error) = link::resolve_kinds(added, &dirs, self.statik) {
+                        folly.link_directives.truncate(first_added);
+                        folly.skip_dependency(
+                            dependency.name,
+                            SkipReason::NotFound(error.to_string()),
+                        );
+                        continue;
+                    }
+                }
+                if let Ok(cflags) = pkg_config.query(pc_name, Query::Cflags) {
+                    folly.add_dependency_cflags(cflags);
+                }
+                continue;
+            }
+
+            let found = dependency.lib_names.iter().find_map(|lib_name| {
+                dependencies::find_library(&lib_dirs, lib_name, self.statik)
+                    .map(|path| (lib_name, path))
+            });
+            if let Some((lib_name, path)) = found {
+                if let Some(dir) = path
+                    .parent()
+                    .filter(|dir| !system_dirs.iter().any(|system_dir| system_dir == dir))
+                {
+                    if !folly.lib_dirs.iter().any(|lib_dir| lib_dir == dir) {
+                        folly.lib_dirs.push(dir.to_owned());
+                    }
+                }
+                folly.link_directives.push(LinkDirective::Lib(LinkLib {
+                    name: (*lib_name).to_owned(),
+                    kind: self.link_kind(),
+                    modifiers: vec![],
+                }));
+                folly.track_file(&path);
+                continue;
+            }
+
+            if required == Some(true) && self.require_dependencies {
+                missing.push(dependency.name.to_owned());
+            } else if let Requirement::Optional = dependency.requirement {
+                // There's no way to tell whether Folly needs it, and usually it doesn't.
+            } else {
+                let error = "no `.pc` file or library file found".to_owned();
+                folly.skip_dependency(dependency.name, SkipReason::NotFound(error));
+            }
         }
+        if !missing.is_empty() {
+            return Err(FollyError::MissingDependencies(missing));
+        }
+        Ok(())
     }
-    if !found_boost_context {
-        return Err(FollyError::BoostContext);
-    }
 
-    let output = Command::new("pkg-config")
-        .args(&["--static", "--cflags", "libfolly"])
-        .output()
-        .map_err(FollyError::MainPackage)?;
-    let output = String::from_utf8(output.stdout).expect("`pkg-config --libs` wasn't UTF-8!");
+    // Unfortunately, just like `fmt` and `gflags`, Folly's `.pc` file doesn't contain link flags
+    // for Boost. What's worse, the names vary based on how Boost was built (`libboost_context.a`
+    // vs. `libboost_context-mt.a` vs. `libboost_context-gcc11-mt-x64-1_79.a`). So find those
+    // libraries manually. We look in the same directories as the Folly installation itself,
+    // followed by any extra search paths the caller supplied, and then the usual places Boost is
+    // installed.
+    //
+    // When linking dynamically, `libfolly.so` already depends on Boost, so Boost is only linked
+    // explicitly if shared copies happen to be lying around, and missing ones aren't reported.
+    fn resolve_boost(&self, folly: &mut Folly) -> Result<(), FollyError> {
+        for var in boost::ENV_VARS {
+            folly.track_env_var(var);
+        }
+        let resolver = BoostResolver::new(
+            folly
+                .lib_dirs
+                .iter()
+                .chain(self.search_paths.iter())
+                .cloned()
+                .collect(),
+            self.statik,
+            folly.sysroot.as_deref(),
+        );
+        for component in boost::COMPONENTS {
+            let name = format!("boost_{}", component);
+            // The CMake export names the Boost libraries itself.
+            let is_component =
+                |lib_name: &str| lib_name == name || lib_name.starts_with(&format!("{}-", name));
+            let already_linked = folly
+                .link_directives
+                .iter()
+                .any(|directive| match *directive {
+                    LinkDirective::Lib(ref lib) => is_component(&lib.name),
+                    // A versioned shared library, passed by its path.
+                    LinkDirective::Arg(ref arg) => Path::new(arg)
+                        .file_name()
+                        .and_then(|file_name| file_name.to_str())
+                        .and_then(|file_name| file_name.strip_prefix("lib"))
+                        .and_then(|file_name| file_name.split('.').next())
+                        .is_some_and(is_component),
+                    LinkDirective::SearchPath(_) => false,
+                });
+            if already_linked {
+                continue;
+            }
+
+            let resolved = match resolver.resolve(component) {
+                // A static link needs a static library; a shared copy doesn't count.
+                Some(resolved) if self.statik && resolved.library.kind != LinkKind::Static => {
+                    Err(format!(
+                        "only found the shared library {}",
+                        resolved.library.path.display()
+                    ))
+                }
+                Some(resolved) => Ok(resolved),
+                None => Err(format!("searched {:?}", resolver.search_dirs())),
+            };
+            let resolved = match resolved {
+                Ok(resolved) => resolved,
+                Err(_) if *component == "context" && self.statik && self.require_boost_context => {
+                    return Err(FollyError::BoostContext)
+                }
+                Err(error) if self.statik => {
+                    folly.skip_dependency(&name, SkipReason::NotFound(error));
+                    continue;
+                }
+                Err(_) => continue,
+            };
+            if let Some(search_dir) = resolved.search_dir {
+                folly
+                    .link_directives
+                    .push(LinkDirective::SearchPath(SearchPath::native(search_dir)));
+            }
+            // When linking dynamically, a static Boost library is skipped rather than used as a
+            // fallback, since `libfolly.so` already brings in its own copy of Boost.
+            if !self.statik && resolved.library.kind != LinkKind::Dylib {
+                continue;
+            }
+            folly.link_directives.push(LinkDirective::Lib(LinkLib {
+                name: resolved.library.name.clone(),
+                kind: self.link_kind(),
+                modifiers: vec![],
+            }));
+            folly.track_file(&resolved.library.path);
+            folly.boost_libraries.push(resolved.library);
+        }
 
-    for arg in output.split_whitespace() {
-        if let Some(rest) = arg.strip_prefix("-I") {
-            let path = Path::new(rest);
-            if path.starts_with("/Library/Developer/CommandLineTools/SDKs")
-                && path.ends_with("usr/include")
+        // Folly's headers include Boost's.
+        let library = folly.boost_libraries.first().map(|library| &*library.path);
+        if let Some(include_dir) = boost::include_dir(library) {
+            // A distribution's Boost is in `/usr/include`, which is searched anyway.
+            if !folly.include_paths.contains(&include_dir)
+                && !folly.is_default_include_dir(&include_dir)
             {
-                // Change any attempt to specify system headers from `-I` to `-isysroot`. `-I` is
-                // not the proper way to include a system header and will cause compilation failures
-                // on macOS Catalina.
-                //
-                // Pop off the trailing `usr/include`.
-                let sysroot = path.parent().unwrap().parent().unwrap();
-                folly.other_cflags.push("-isysroot".to_owned());
-                folly.other_cflags.push(sysroot.to_string_lossy().into_owned());
-            } else {
-                folly.include_paths.push(path.to_owned());
+                folly.include_paths.push(include_dir);
             }
         }
+        Ok(())
     }
 
-    Ok(folly)
+    // Folly's `.pc` file is missing the `fmt` and `gflags` dependencies. Find them here, using
+    // Folly's configuration header, if it can be found, to decide which are needed.
+    //
+    // We call `pkg-config` ourselves rather than using the `pkg-config` crate, because that crate
+    // doesn't successfully parse some of Folly's dependencies: it passes the raw `.so` files
+    // instead of using `-l` flags. This also lets us fall back to reading `.pc` files directly
+    // when there's no `pkg-config` binary.
+    fn probe_pkg_config_dependencies(
+        &self,
+        pkg_config: &PkgConfig,
+        folly: &mut Folly,
+    ) -> Result<(), FollyError> {
+        for var in query::ENV_VARS {
+            folly.track_env_var(var);
+        }
+        let config_header = match folly.find_config_header() {
+            Some(path) => Some(ConfigHeader::read(&path)?),
+            None => None,
+        };
+
+        let fmt_header_only = folly
+            .defines
+            .iter()
+            .any(|(name, _)| name == "FMT_HEADER_ONLY")
+            || config_header
+                .as_ref()
+                .is_some_and(|config_header| config_header.is_set("FMT_HEADER_ONLY"));
+        if !self.require_fmt {
+            folly.skip_dependency("fmt", SkipReason::NotRequested);
+        } else if fmt_header_only {
+            folly.skip_dependency("fmt", SkipReason::HeaderOnly);
+        } else {
+            for path in pkg_config.pc_files("fmt") {
+                folly.track_file(&path);
+            }
+            let libs = pkg_config
+                .query("fmt", Query::Libs)
+                .map_err(|error| FollyError::FmtDependency(Box::new(error)))?;
+            folly.add_libs(libs, self.link_kind());
+            if let Ok(cflags) = pkg_config.query("fmt", Query::Cflags) {
+                folly.add_dependency_cflags(cflags);
+            }
+        }
+
+        let uses_gflags = config_header
+            .as_ref()
+            .map(|config_header| config_header.features().gflags);
+        if !self.require_gflags {
+            folly.skip_dependency("gflags", SkipReason::NotRequested);
+        } else if uses_gflags == Some(false) {
+            folly.skip_dependency("gflags", SkipReason::NotUsed);
+        } else {
+            for path in pkg_config.pc_files("gflags") {
+                folly.track_file(&path);
+            }
+            match pkg_config.query("gflags", Query::Libs) {
+                Ok(libs) => {
+                    folly.add_libs(libs, self.link_kind());
+                    if let Ok(cflags) = pkg_config.query("gflags", Query::Cflags) {
+                        folly.add_dependency_cflags(cflags);
+                    }
+                }
+                Err(error) if uses_gflags.is_none() => {
+                    folly.skip_dependency("gflags", SkipReason::NotFound(error.to_string()))
+                }
+                Err(error) => return Err(FollyError::GflagsDependency(Box::new(error))),
+            }
+        }
+        Ok(())
+    }
+
+    fn probe_cmake(&self) -> Result<Folly, FollyError> {
+        let package = cmake_package::find(self.statik)
+            .map_err(|error| FollyError::MainPackage(Box::new(error)))?;
+        let mut folly = self.folly_from_cmake_package(package);
+        for var in cmake_package::ENV_VARS {
+            folly.track_env_var(var);
+        }
+        Ok(folly)
+    }
+
+    fn folly_from_cmake_package(&self, package: CMakePackage) -> Folly {
+        let mut folly = Folly::new();
+        folly.add_libs(package.libs, self.link_kind());
+        for cflag in cflags::classify_cflags(package.cflags) {
+            folly.add_cflag(cflag);
+        }
+        for path in &package.files {
+            folly.track_file(path);
+        }
+        folly.cmake_dir = Some(package.config_dir);
+        folly.version = package.version.and_then(|version| version.parse().ok());
+        folly
+    }
+
+    // Locates the Folly installed under `prefix`, preferring its `.pc` file, then its CMake
+    // package configuration, and finally assuming the standard layout.
+    fn probe_prefix(&self, prefix: &Path) -> Result<Folly, FollyError> {
+        let pc_dirs: Vec<_> = ["lib/pkgconfig", "lib64/pkgconfig", "share/pkgconfig"]
+            .iter()
+            .map(|dir| prefix.join(dir))
+            .filter(|dir| dir.is_dir())
+            .collect();
+        if pc_dirs.iter().any(|dir| dir.join("libfolly.pc").is_file()) {
+            return self.probe_pkg_config(pc_dirs);
+        }
+
+        match cmake_package::find_in(&cmake_package::config_dirs_under(prefix), self.statik) {
+            Ok(package) => Ok(self.folly_from_cmake_package(package)),
+            Err(FollyError::CMakePackageNotFound { .. }) => {
+                let lib_dir = ["lib64", "lib"]
+                    .iter()
+                    .map(|dir| prefix.join(dir))
+                    .find(|dir| dir.is_dir())
+                    .unwrap_or_else(|| prefix.join("lib"));
+                self.probe_explicit(Some(lib_dir), Some(prefix.join("include")), pc_dirs)
+            }
+            Err(error) => Err(FollyError::MainPackage(Box::new(error))),
+        }
+    }
+
+    // Skips discovery of Folly itself and uses the given directories.
+    fn probe_explicit(
+        &self,
+        lib_dir: Option<PathBuf>,
+        include_dir: Option<PathBuf>,
+        pc_dirs: Vec<PathBuf>,
+    ) -> Result<Folly, FollyError> {
+        let mut folly = Folly::new();
+        folly.link_directives.push(LinkDirective::Lib(LinkLib {
+            name: "folly".to_owned(),
+            kind: self.link_kind(),
+            modifiers: vec![],
+        }));
+        folly.lib_dirs.extend(lib_dir);
+        folly.include_paths.extend(include_dir);
+
+        // As with `libfolly.pc`, the sysroot's `folly-config.h` decides which dependencies are
+        // needed.
+        folly.detect_sysroot();
+        let pkg_config = PkgConfig::new(self.statik).search_dirs_first(pc_dirs);
+        self.probe_pkg_config_dependencies(&pkg_config, &mut folly)?;
+        Ok(folly)
+    }
+
+    // The link kind to use for libraries that the linker would otherwise choose for itself.
+    fn link_kind(&self) -> Option<LinkKind> {
+        if self.statik {
+            None
+        } else {
+            Some(LinkKind::Dylib)
+        }
+    }
+}
+
+fn describe_exit_code(code: Option<i32>) -> String {
+    match code {
+        Some(code) => format!("exit code {}", code),
+        None => "terminated by a signal".to_owned(),
+    }
+}
+
+fn describe_names(names: &[String]) -> String {
+    let names: Vec<_> = names.iter().map(|name| format!("`{}`", name)).collect();
+    names.join(", ")
+}
+
+fn describe_env_var(value: Option<&OsString>) -> String {
+    match value {
+        Some(value) => format!("is `{}`", value.to_string_lossy()),
+        None => "is unset".to_owned(),
+    }
+}
+
+// Returns the value of a flag such as `-L`: the rest of the argument if there is any, and
+// otherwise the next argument.
+fn flag_value(rest: OsString, args: &mut impl Iterator<Item = OsString>) -> Option<OsString> {
+    if rest.is_empty() {
+        args.next()
+    } else {
+        Some(rest)
+    }
+}
+
+impl Default for FollyProbe {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Folly {
@@ -163,8 +1082,539 @@ impl Folly {
         Self {
             lib_dirs: vec![],
             include_paths: vec![],
+            system_include_paths: vec![],
+            defines: vec![],
+            cpp_std: None,
             other_cflags: vec![],
-            _priv: (),
+            link_directives: vec![],
+            cmake_dir: None,
+            version: None,
+            config_header: None,
+            features: None,
+            boost_libraries: vec![],
+            skipped_dependencies: vec![],
+            warnings: vec![],
+            rerun_if_changed: vec![],
+            rerun_if_env_changed: vec![],
+            sysroot: None,
+            emitted: AtomicBool::new(false),
+        }
+    }
+
+    /// Prints the `cargo:` directives needed to link against Folly to standard output.
+    ///
+    /// [`FollyProbe::probe()`] calls this automatically unless `cargo_metadata(false)` was set.
+    ///
+    /// [`Folly::warnings`] and optional dependencies that were skipped because they couldn't be
+    /// found are reported with `cargo:warning`, since the link may fail without them.
+    ///
+    /// This includes `cargo:rerun-if-changed` directives. Once a build script prints any of those,
+    /// Cargo no longer reruns it whenever a file in the package changes, so a build script that
+    /// compiles C++ sources should print `cargo:rerun-if-changed` for those sources as well.
+    pub fn emit_cargo_metadata(&self) {
+        self.emitted.store(true, Ordering::Relaxed);
+        for directive in &self.link_directives {
+            println!("cargo:{}", directive);
+        }
+        for path in &self.rerun_if_changed {
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+        for var in &self.rerun_if_env_changed {
+            println!("cargo:rerun-if-env-changed={}", var);
+        }
+        for warning in &self.warnings {
+            println!("cargo:warning={}", warning.replace('\n', " "));
+        }
+        for skipped in &self.skipped_dependencies {
+            if let SkipReason::NotFound(_) = skipped.reason {
+                println!("cargo:warning={}", skipped.to_string().replace('\n', " "));
+            }
+        }
+    }
+
+    /// Prints `cargo:rustc-cfg` directives describing this installation, so that Rust code can be
+    /// compiled conditionally on it.
+    ///
+    /// For each enabled feature in [`Folly::features`], `folly_has_<feature>` is set, for example
+    /// `folly_has_uring` or `folly_has_coro`. For each version in `version_thresholds` that the
+    /// installed version is at least, `folly_version_at_least="<version>"` is set:
+    ///
+    /// ```ignore
+    /// folly.emit_rustc_cfgs(&["2022.08.29"])?;
+    /// ...
+    /// #[cfg(all(folly_has_coro, folly_version_at_least = "2022.08.29"))]
+    /// mod coro;
+    /// ```
+    ///
+    /// Every cfg that could be set is also declared with `cargo:rustc-check-cfg`, so that none of
+    /// them trigger the `unexpected_cfgs` lint. This isn't called by [`FollyProbe::probe()`].
+    pub fn emit_rustc_cfgs(&self, version_thresholds: &[&str]) -> Result<(), FollyError> {
+        for line in self.rustc_cfg_lines(version_thresholds)? {
+            println!("{}", line);
+        }
+        Ok(())
+    }
+
+    // The lines `emit_rustc_cfgs()` prints. Every threshold is parsed before anything is printed,
+    // so an invalid one doesn't leave a partial set of cfgs behind.
+    fn rustc_cfg_lines(&self, version_thresholds: &[&str]) -> Result<Vec<String>, FollyError> {
+        let thresholds = version_thresholds
+            .iter()
+            .map(|threshold| Ok((threshold, threshold.parse::<FollyVersion>()?)))
+            .collect::<Result<Vec<_>, FollyError>>()?;
+
+        let mut lines = vec![];
+        let features = self.features.clone().unwrap_or_default();
+        for (name, enabled) in features.by_name() {
+            lines.push(format!("cargo:rustc-check-cfg=cfg(folly_has_{})", name));
+            if enabled {
+                lines.push(format!("cargo:rustc-cfg=folly_has_{}", name));
+            }
+        }
+
+        let mut values = vec![];
+        for (threshold, threshold_version) in thresholds {
+            values.push(format!("{:?}", threshold));
+            if self
+                .version
+                .as_ref()
+                .is_some_and(|version| *version >= threshold_version)
+            {
+                lines.push(format!(
+                    "cargo:rustc-cfg=folly_version_at_least={:?}",
+                    threshold
+                ));
+            }
+        }
+        if !values.is_empty() {
+            lines.push(format!(
+                "cargo:rustc-check-cfg=cfg(folly_version_at_least, values({}))",
+                values.join(", ")
+            ));
+        }
+        Ok(lines)
+    }
+
+    /// Returns the arguments clang needs to parse Folly's headers, for use with `bindgen` or
+    /// other libclang-based tools.
+    ///
+    /// These are the same flags that `Folly::apply_to()`, with the `cc` feature, gives the C++
+    /// compiler, plus `-x c++`: the C++ standard, the include paths of Folly and of dependencies
+    /// whose headers Folly's headers include, such as fmt, glog, and Boost, Folly's defines, and
+    /// its other flags, including any `-isysroot`.
+    pub fn clang_args(&self) -> Vec<String> {
+        let mut args = vec![
+            "-x".to_owned(),
+            "c++".to_owned(),
+            format!(
+                "-std={}",
+                self.cpp_std.as_deref().unwrap_or(cflags::DEFAULT_CPP_STD)
+            ),
+        ];
+        for path in self.header_dirs() {
+            args.push("-isystem".to_owned());
+            args.push(path.to_string_lossy().into_owned());
+        }
+        for (name, value) in &self.defines {
+            args.push(match *value {
+                Some(ref value) => format!("-D{}={}", name, value),
+                None => format!("-D{}", name),
+            });
         }
+        args.extend(self.other_cflags.iter().cloned());
+        args
+    }
+
+    // The directories to search for Folly's headers and its dependencies' headers, leaving out
+    // the ones the compiler searches anyway.
+    pub(crate) fn header_dirs(&self) -> impl Iterator<Item = &Path> {
+        self.include_paths
+            .iter()
+            .chain(&self.system_include_paths)
+            .map(PathBuf::as_path)
+            .filter(|path| !self.is_default_include_dir(path))
+    }
+
+    // Returns true if the compiler searches `path` for headers without being told to, in which
+    // case it mustn't be passed explicitly. See `cflags::DEFAULT_INCLUDE_DIRS`.
+    fn is_default_include_dir(&self, path: &Path) -> bool {
+        cflags::DEFAULT_INCLUDE_DIRS
+            .iter()
+            .any(|dir| Path::new(dir) == path)
+            || self
+                .sysroot
+                .as_deref()
+                .is_some_and(|sysroot| sysroot::is_default_include_dir(path, sysroot))
+    }
+
+    // Finds `folly/folly-config.h` in the include paths, or in the default include paths that
+    // `pkg-config` strips.
+    fn find_config_header(&self) -> Option<PathBuf> {
+        let default_dirs = cflags::DEFAULT_INCLUDE_DIRS
+            .iter()
+            .rev()
+            .map(|dir| sysroot::under(self.sysroot.as_deref(), Path::new(dir)));
+        self.include_paths
+            .iter()
+            .chain(self.system_include_paths.iter())
+            .cloned()
+            .chain(default_dirs)
+            .map(|dir| dir.join("folly").join("folly-config.h"))
+            .find(|path| path.is_file())
+    }
+
+    // Works out the sysroot from Folly's flags and the environment, unless that's already been
+    // done. This has to happen before anything looks for headers or libraries in the default
+    // directories, which are under the sysroot.
+    fn detect_sysroot(&mut self) {
+        for var in sysroot::ENV_VARS {
+            self.track_env_var(var);
+        }
+        if self.sysroot.is_some() {
+            return;
+        }
+        let pkg_config_sysroot_dir = env::var_os("PKG_CONFIG_SYSROOT_DIR").map(PathBuf::from);
+        // `SDKROOT` is only meaningful when building for an Apple platform.
+        let sdkroot = env::var_os("SDKROOT")
+            .map(PathBuf::from)
+            .filter(|_| env::var("CARGO_CFG_TARGET_VENDOR").is_ok_and(|vendor| vendor == "apple"));
+        if let Some(sysroot) = sysroot::detect(
+            &self.other_cflags,
+            &self.include_paths,
+            pkg_config_sysroot_dir.as_deref(),
+            sdkroot.as_deref(),
+        ) {
+            self.set_sysroot(sysroot);
+        }
+    }
+
+    // Records the sysroot. Its own header directories come out of the include paths, since the
+    // compiler searches them by itself once it's told about the sysroot, and the compiler and
+    // linker flags that tell it are added.
+    fn set_sysroot(&mut self, sysroot: PathBuf) {
+        self.include_paths
+            .retain(|path| !sysroot::is_default_include_dir(path, &sysroot));
+        self.system_include_paths
+            .retain(|path| !sysroot::is_default_include_dir(path, &sysroot));
+        if sysroot::from_cflags(&self.other_cflags).is_none() {
+            self.other_cflags.extend(sysroot::cflags(&sysroot));
+        }
+        if let Some(arg) = sysroot::link_arg(&sysroot) {
+            self.link_directives.push(LinkDirective::Arg(arg));
+        }
+        self.sysroot = Some(sysroot);
+    }
+
+    // Whether Folly's compiler flags turn on C++ coroutines, which `folly::coro` depends on.
+    fn coroutines_enabled(&self) -> bool {
+        let std_has_coroutines = self.cpp_std.as_deref().is_some_and(|std| {
+            let version = std.trim_start_matches("gnu++").trim_start_matches("c++");
+            matches!(version, "20" | "2a" | "23" | "2b" | "26" | "2c")
+        });
+        std_has_coroutines
+            || self
+                .other_cflags
+                .iter()
+                .any(|flag| flag == "-fcoroutines" || flag == "-fcoroutines-ts")
+    }
+
+    // If `dependency` is already linked, returns true, first correcting the name it's linked by
+    // if no library by that name exists but one by another of its names does. A library passed by
+    // its path or file name counts as linked, and is left alone.
+    fn fix_dependency_name(
+        &mut self,
+        dependency: &Dependency,
+        lib_dirs: &[PathBuf],
+        statik: bool,
+    ) -> bool {
+        let linked = self.link_directives.iter_mut().find(|directive| {
+            link::lib_name(directive).is_some_and(|name| dependency.lib_names.contains(&name))
+        });
+        let linked = match linked {
+            Some(LinkDirective::Lib(linked))
+                if !linked.modifiers.contains(&LinkModifier::Verbatim(true)) =>
+            {
+                linked
+            }
+            Some(_) => return true,
+            None => return false,
+        };
+        if dependencies::find_library(lib_dirs, &linked.name, statik).is_none() {
+            if let Some(lib_name) = dependency
+                .lib_names
+                .iter()
+                .find(|lib_name| dependencies::find_library(lib_dirs, lib_name, statik).is_some())
+            {
+                linked.name = (*lib_name).to_owned();
+            }
+        }
+        true
+    }
+
+    fn skip_dependency(&mut self, name: &str, reason: SkipReason) {
+        self.skipped_dependencies.push(SkippedDependency {
+            name: name.to_owned(),
+            reason,
+        });
+    }
+
+    fn track_file(&mut self, path: &Path) {
+        if !self.rerun_if_changed.iter().any(|tracked| tracked == path) {
+            self.rerun_if_changed.push(path.to_owned());
+        }
+    }
+
+    fn track_env_var(&mut self, var: &str) {
+        if !self
+            .rerun_if_env_changed
+            .iter()
+            .any(|tracked| tracked == var)
+        {
+            self.rerun_if_env_changed.push(var.to_owned());
+        }
+    }
+
+    fn add_cflag(&mut self, cflag: Cflag) {
+        match cflag {
+            // An SDK's `usr/include` is replaced by `-isysroot` once the probe works out the
+            // sysroot.
+            Cflag::Include(path) => self.include_paths.push(path),
+            Cflag::SystemInclude(path) => self.system_include_paths.push(path),
+            Cflag::Define(name, value) => self.defines.push((name, value)),
+            Cflag::Std(std) => self.cpp_std = Some(std),
+            Cflag::Other(flags) => self.other_cflags.extend(flags),
+        }
+    }
+
+    // Adds the include paths and defines from a dependency's `pkg-config --cflags`, since Folly's
+    // headers include the dependency's headers. Its other flags are left out; Folly's own flags
+    // decide the language standard and the like.
+    fn add_dependency_cflags(&mut self, cflags: Vec<OsString>) {
+        for cflag in cflags::classify_cflags(cflags) {
+            match cflag {
+                Cflag::Include(path) => {
+                    if !self.include_paths.contains(&path) && !self.is_default_include_dir(&path) {
+                        self.include_paths.push(path);
+                    }
+                }
+                Cflag::SystemInclude(path) => {
+                    if !self.system_include_paths.contains(&path)
+                        && !self.is_default_include_dir(&path)
+                    {
+                        self.system_include_paths.push(path);
+                    }
+                }
+                Cflag::Define(name, value) => {
+                    if !self.defines.iter().any(|(existing, _)| *existing == name) {
+                        self.defines.push((name, value));
+                    }
+                }
+                Cflag::Std(_) | Cflag::Other(_) => {}
+            }
+        }
+    }
+
+    // Handles the output of `pkg-config --libs`.
+    fn add_libs(&mut self, args: Vec<OsString>, kind: Option<LinkKind>) {
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if query::strip_flag(&arg, "-").is_none() {
+                self.add_library_path(Path::new(&arg), kind);
+                continue;
+            }
+
+            // `-framework` and `-Xlinker` always take their value as a separate argument. `-L`,
+            // `-F`, and `-l` usually don't, but may.
+            if arg == "-framework" {
+                if let Some(name) = args.next() {
+                    self.link_directives
+                        .push(LinkDirective::Lib(LinkLib::with_kind(
+                            name.to_string_lossy(),
+                            LinkKind::Framework,
+                        )));
+                }
+            } else if arg == "-Xlinker" {
+                if let Some(value) = args.next() {
+                    for arg in [arg, value] {
+                        let arg = arg.to_string_lossy().into_owned();
+                        self.link_directives.push(LinkDirective::Arg(arg));
+                    }
+                }
+            } else if let Some(rest) = query::strip_flag(&arg, "-L") {
+                self.lib_dirs
+                    .extend(flag_value(rest, &mut args).map(PathBuf::from));
+            } else if let Some(rest) = query::strip_flag(&arg, "-F") {
+                if let Some(dir) = flag_value(rest, &mut args) {
+                    self.link_directives
+                        .push(LinkDirective::SearchPath(SearchPath::framework(dir)));
+                }
+            } else if let Some(rest) = query::strip_flag(&arg, "-l") {
+                let name = match flag_value(rest, &mut args) {
+                    Some(name) => name.to_string_lossy().into_owned(),
+                    None => continue,
+                };
+                // `-l:libfoo.a` names a file rather than a library.
+                let lib = match name.strip_prefix(':') {
+                    Some(file_name) => LinkLib {
+                        name: file_name.to_owned(),
+                        kind: Some(if file_name.ends_with(".a") {
+                            LinkKind::Static
+                        } else {
+                            LinkKind::Dylib
+                        }),
+                        modifiers: vec![LinkModifier::Verbatim(true)],
+                    },
+                    None => LinkLib {
+                        name,
+                        kind,
+                        modifiers: vec![],
+                    },
+                };
+                self.link_directives.push(LinkDirective::Lib(lib));
+            } else {
+                // Anything else, such as `-pthread`, `-rdynamic`, or `-Wl,--as-needed`, is meant
+                // for the compiler driver that rustc links with, so it's passed through as is.
+                self.link_directives
+                    .push(LinkDirective::Arg(arg.to_string_lossy().into_owned()));
+            }
+        }
+    }
+
+    // Links against a library that was specified by its full path rather than with `-l`.
+    //
+    // `kind` is the kind used for `-l` flags. If the file is of the other kind, the linker is told
+    // so explicitly, since otherwise it may pick up a different file with the same name.
+    fn add_library_path(&mut self, path: &Path, kind: Option<LinkKind>) {
+        match link::classify_library_path(path) {
+            LibraryPath::Named {
+                dir,
+                name,
+                kind: file_kind,
+            } => {
+                let default_kind = kind.unwrap_or(LinkKind::Static);
+                let kind = match file_kind {
+                    Some(file_kind) if file_kind != default_kind => Some(file_kind),
+                    _ => kind,
+                };
+                self.link_directives
+                    .push(LinkDirective::SearchPath(SearchPath::native(dir)));
+                self.link_directives.push(LinkDirective::Lib(LinkLib {
+                    name,
+                    kind,
+                    modifiers: vec![],
+                }));
+            }
+            LibraryPath::Verbatim => self
+                .link_directives
+                .push(LinkDirective::Arg(path.to_string_lossy().into_owned())),
+            LibraryPath::Unrecognized => {
+                self.warnings.push(format!(
+                    "passing `{}` to the linker as is, since it doesn't look like a library",
+                    path.display()
+                ));
+                self.link_directives
+                    .push(LinkDirective::Arg(path.to_string_lossy().into_owned()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folly(version: Option<&str>, features: Option<FollyFeatures>) -> Folly {
+        let mut folly = Folly::new();
+        folly.version = version.map(|version| version.parse().unwrap());
+        folly.features = features;
+        folly
+    }
+
+    fn dependency(name: &str) -> &'static Dependency {
+        dependencies::DEPENDENCIES
+            .iter()
+            .find(|dependency| dependency.name == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn fix_dependency_name_recognizes_paths_and_verbatim_libraries() {
+        let versioned =
+            LinkDirective::Arg("/usr/lib/x86_64-linux-gnu/libdouble-conversion.so.3".to_owned());
+        let verbatim = LinkDirective::Lib(LinkLib {
+            name: "libglog.a".to_owned(),
+            kind: Some(LinkKind::Static),
+            modifiers: vec![LinkModifier::Verbatim(true)],
+        });
+        let mut folly = folly(None, None);
+        folly.link_directives = vec![versioned.clone(), verbatim.clone()];
+        assert!(folly.fix_dependency_name(dependency("double-conversion"), &[], true));
+        assert!(folly.fix_dependency_name(dependency("glog"), &[], true));
+        assert!(!folly.fix_dependency_name(dependency("libevent"), &[], true));
+        assert_eq!(folly.link_directives, [versioned, verbatim]);
+    }
+
+    #[test]
+    fn fix_dependency_name_recognizes_named_libraries() {
+        let mut folly = folly(None, None);
+        folly.link_directives = vec![LinkDirective::Lib(LinkLib {
+            name: "double_conversion".to_owned(),
+            kind: None,
+            modifiers: vec![],
+        })];
+        assert!(folly.fix_dependency_name(dependency("double-conversion"), &[], true));
+    }
+
+    #[test]
+    fn rustc_cfg_lines_describe_features_and_versions() {
+        let features = FollyFeatures {
+            uring: true,
+            coroutines: true,
+            ..FollyFeatures::default()
+        };
+        let lines = folly(Some("2023.05.22.00"), Some(features))
+            .rustc_cfg_lines(&["2022.08.29", "2023.05.22.00", "2024.01.01"])
+            .unwrap();
+        assert!(lines.contains(&"cargo:rustc-check-cfg=cfg(folly_has_gflags)".to_owned()));
+        assert!(!lines.contains(&"cargo:rustc-cfg=folly_has_gflags".to_owned()));
+        assert!(lines.contains(&"cargo:rustc-cfg=folly_has_uring".to_owned()));
+        assert!(lines.contains(&"cargo:rustc-cfg=folly_has_coro".to_owned()));
+        let version_lines: Vec<_> = lines
+            .iter()
+            .filter(|line| line.contains("folly_version_at_least"))
+            .collect();
+        assert_eq!(
+            version_lines,
+            [
+                "cargo:rustc-cfg=folly_version_at_least=\"2022.08.29\"",
+                "cargo:rustc-cfg=folly_version_at_least=\"2023.05.22.00\"",
+                "cargo:rustc-check-cfg=cfg(folly_version_at_least, \
+                    values(\"2022.08.29\", \"2023.05.22.00\", \"2024.01.01\"))",
+            ]
+        );
+    }
+
+    #[test]
+    fn rustc_cfg_lines_without_a_version_only_declare_thresholds() {
+        let lines = folly(None, None).rustc_cfg_lines(&["2022.08.29"]).unwrap();
+        assert!(!lines
+            .iter()
+            .any(|line| line.starts_with("cargo:rustc-cfg=")));
+        assert_eq!(
+            lines.last().map(String::as_str),
+            Some("cargo:rustc-check-cfg=cfg(folly_version_at_least, values(\"2022.08.29\"))")
+        );
+        let lines = folly(None, None).rustc_cfg_lines(&[]).unwrap();
+        assert!(!lines
+            .iter()
+            .any(|line| line.contains("folly_version_at_least")));
+    }
+
+    #[test]
+    fn rustc_cfg_lines_reject_an_invalid_threshold() {
+        let result = folly(Some("2023.05.22.00"), None).rustc_cfg_lines(&["2022.08.29", "next"]);
+        assert!(
+            matches!(result, Err(FollyError::InvalidVersion(ref version)) if version == "next")
+        );
     }
 }
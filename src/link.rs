@@ -0,0 +1,774 @@
+// find-folly/src/link.rs
+//
+//! Typed descriptions of the linker directives needed to link against Folly.
+//!
+//! Nothing in this module prints anything. A [`LinkDirective`] is only turned into a `cargo:` line
+//! when [`crate::Folly::emit_cargo_metadata()`] is called, so build scripts are free to inspect,
+//! filter, or reorder the directives first.
+
+use crate::dependencies;
+use crate::pc_file;
+use crate::sysroot;
+use crate::FollyError;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::mem;
+use std::path::{Path, PathBuf};
+
+/// A single instruction to the linker, corresponding to one `cargo:` line.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LinkDirective {
+    /// A directory to add to the library search path (`cargo:rustc-link-search`).
+    SearchPath(SearchPath),
+    /// A library to link against (`cargo:rustc-link-lib`).
+    Lib(LinkLib),
+    /// A raw argument to pass to the linker (`cargo:rustc-link-arg`), such as `-pthread` or
+    /// `-Wl,--as-needed`.
+    ///
+    /// Unlike libraries, Cargo only passes these on when linking the package's own binaries,
+    /// tests, and examples, not those of packages that depend on it.
+    Arg(String),
+}
+
+/// A directory to add to the library search path.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SearchPath {
+    pub kind: SearchKind,
+    pub path: PathBuf,
+}
+
+/// Which kinds of libraries a [`SearchPath`] applies to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SearchKind {
+    /// Native libraries (`native=`).
+    Native,
+    /// macOS frameworks (`framework=`).
+    Framework,
+}
+
+/// A library to link against.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LinkLib {
+    /// The name of the library, without any `lib` prefix or file extension.
+    pub name: String,
+    /// How to link the library. `None` lets the linker decide.
+    pub kind: Option<LinkKind>,
+    /// Linking modifiers such as `+whole-archive`. These are only valid if `kind` is set.
+    pub modifiers: Vec<LinkModifier>,
+}
+
+/// How a library is linked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LinkKind {
+    Static,
+    Dylib,
+    Framework,
+}
+
+/// A linking modifier, as accepted by `rustc -l KIND:MODIFIERS=NAME`. The boolean indicates
+/// whether the modifier is enabled (`+`) or disabled (`-`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LinkModifier {
+    WholeArchive(bool),
+    Bundle(bool),
+    Verbatim(bool),
+    AsNeeded(bool),
+}
+
+impl LinkLib {
+    /// Creates a library directive with no explicit kind or modifiers.
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        Self {
+            name: name.into(),
+            kind: None,
+            modifiers: vec![],
+        }
+    }
+
+    /// Adds `modifier`, replacing any existing setting of the same modifier.
+    pub fn set_modifier(&mut self, modifier: LinkModifier) {
+        self.modifiers
+            .retain(|existing| mem::discriminant(existing) != mem::discriminant(&modifier));
+        self.modifiers.push(modifier);
+    }
+
+    /// Creates a library directive with the given kind and no modifiers.
+    pub fn with_kind<S: Into<String>>(name: S, kind: LinkKind) -> Self {
+        Self {
+            name: name.into(),
+            kind: Some(kind),
+            modifiers: vec![],
+        }
+    }
+}
+
+impl SearchPath {
+    /// Creates a search path for native libraries.
+    pub fn native<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            kind: SearchKind::Native,
+            path: path.into(),
+        }
+    }
+
+    /// Creates a search path for macOS frameworks.
+    pub fn framework<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            kind: SearchKind::Framework,
+            path: path.into(),
+        }
+    }
+}
+
+/// Formats the directive as it appears after `cargo:` in build script output.
+impl Display for LinkDirective {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match *self {
+            LinkDirective::SearchPath(ref search_path) => {
+                write!(f, "rustc-link-search={}", search_path)
+            }
+            LinkDirective::Lib(ref lib) => write!(f, "rustc-link-lib={}", lib),
+            LinkDirective::Arg(ref arg) => write!(f, "rustc-link-arg={}", arg),
+        }
+    }
+}
+
+impl Display for SearchPath {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let kind = match self.kind {
+            SearchKind::Native => "native",
+            SearchKind::Framework => "framework",
+        };
+        write!(f, "{}={}", kind, self.path.display())
+    }
+}
+
+impl Display for LinkLib {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        if let Some(kind) = self.kind {
+            f.write_str(match kind {
+                LinkKind::Static => "static",
+                LinkKind::Dylib => "dylib",
+                LinkKind::Framework => "framework",
+            })?;
+            for (index, modifier) in self.modifiers.iter().enumerate() {
+                write!(f, "{}{}", if index == 0 { ':' } else { ',' }, modifier)?;
+            }
+            f.write_str("=")?;
+        }
+        f.write_str(&self.name)
+    }
+}
+
+impl Display for LinkModifier {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let (enabled, name) = match *self {
+            LinkModifier::WholeArchive(enabled) => (enabled, "whole-archive"),
+            LinkModifier::Bundle(enabled) => (enabled, "bundle"),
+            LinkModifier::Verbatim(enabled) => (enabled, "verbatim"),
+            LinkModifier::AsNeeded(enabled) => (enabled, "as-needed"),
+        };
+        write!(f, "{}{}", if enabled { '+' } else { '-' }, name)
+    }
+}
+
+/// How a library that was specified by its full path can be passed to the linker.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum LibraryPath {
+    /// The linker finds the library with `-l<name>` when `dir` is searched. `kind` is `None` if
+    /// the file name doesn't say whether the library is static or shared, as with `.lib` files.
+    Named {
+        dir: PathBuf,
+        name: String,
+        kind: Option<LinkKind>,
+    },
+    /// A library with no `-l` form, such as `libfoo.so.1.2` or `foo.a`, which has to be passed to
+    /// the linker as a path.
+    Verbatim,
+    /// Something that isn't recognizably a library.
+    Unrecognized,
+}
+
+/// Works out how to link against the library at `path`.
+pub(crate) fn classify_library_path(path: &Path) -> LibraryPath {
+    let (dir, file_name) = match (
+        path.parent(),
+        path.file_name().and_then(|name| name.to_str()),
+    ) {
+        (Some(dir), Some(file_name)) => (dir, file_name),
+        _ => return LibraryPath::Unrecognized,
+    };
+    let named = |name: &str, kind| LibraryPath::Named {
+        dir: dir.to_owned(),
+        name: name.to_owned(),
+        kind,
+    };
+
+    // MSVC libraries are linked by their stem, whatever it is, and can be static libraries or
+    // import libraries.
+    if let Some(stem) = file_name.strip_suffix(".lib") {
+        return if stem.is_empty() {
+            LibraryPath::Unrecognized
+        } else {
+            named(stem, None)
+        };
+    }
+
+    let suffixes = [
+        (".a", LinkKind::Static),
+        (".so", LinkKind::Dylib),
+        (".dylib", LinkKind::Dylib),
+        (".tbd", LinkKind::Dylib),
+    ];
+    for (suffix, kind) in suffixes {
+        let stem = match file_name.strip_suffix(suffix) {
+            Some(stem) => stem,
+            None => continue,
+        };
+        return match stem.strip_prefix("lib") {
+            Some(name) if !name.is_empty() => named(name, Some(kind)),
+            _ => LibraryPath::Verbatim,
+        };
+    }
+
+    // A versioned shared library, such as `libfoo.so.1.2`.
+    if let Some((_, version)) = file_name.split_once(".so.") {
+        if version
+            .split('.')
+            .all(|part| !part.is_empty() && part.bytes().all(|byte| byte.is_ascii_digit()))
+        {
+            return LibraryPath::Verbatim;
+        }
+    }
+    LibraryPath::Unrecognized
+}
+
+/// Returns the directories the linker searches without being told to, under `sysroot` if there is
+/// one.
+pub(crate) fn system_lib_dirs(sysroot: Option<&Path>) -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    if let Some(multiarch) = pc_file::multiarch_triple() {
+        dirs.push(Path::new("/usr/lib").join(&multiarch));
+        dirs.push(Path::new("/lib").join(&multiarch));
+    }
+    dirs.extend(
+        ["/usr/local/lib", "/usr/lib64", "/usr/lib"]
+            .iter()
+            .map(PathBuf::from),
+    );
+    dirs.iter()
+        .map(|dir| sysroot::under(sysroot, dir))
+        .collect()
+}
+
+/// Libraries that come with the C and C++ toolchains. Anything else may depend on them, so they're
+/// linked last.
+const SYSTEM_LIBS: &[&str] = &[
+    "c", "m", "dl", "rt", "pthread", "util", "resolv", "atomic", "stdc++", "stdc++fs", "c++",
+    "c++abi", "gcc", "gcc_s",
+];
+
+/// Puts the directives collected while probing into the order they're emitted in.
+///
+/// Search paths come first, without duplicates. Libraries follow: Folly's own, then its
+/// dependencies, then the system libraries that anything may depend on. A library that's listed
+/// more than once, whether by name or by path, keeps its last position, after everything that
+/// depends on it, as a static link requires. The dependencies that
+/// [`dependencies::link_position()`] knows about, such as `glog` and `gflags`, are then put in that
+/// order, after the other dependencies, since `pkg-config` and CMake exports don't always list
+/// them in an order that links. Linker flags come last, in their original order.
+///
+/// If `group` is true, the libraries are passed as linker arguments instead, with the static ones
+/// between `--start-group` and `--end-group` so that the linker resolves references among them
+/// whatever their order.
+pub(crate) fn consolidate(directives: Vec<LinkDirective>, group: bool) -> Vec<LinkDirective> {
+    let mut search_paths = vec![];
+    let mut libs: Vec<LinkDirective> = vec![];
+    let mut flags = vec![];
+    let mut directives = directives.into_iter();
+    while let Some(directive) = directives.next() {
+        match directive {
+            LinkDirective::SearchPath(_) => {
+                if !search_paths.contains(&directive) {
+                    search_paths.push(directive);
+                }
+            }
+            LinkDirective::Arg(ref arg) if arg.starts_with('-') => {
+                // `-Xlinker` applies to the argument after it, and `-Wl,` flags such as
+                // `-Wl,-rpath` often do too, so only flags that stand alone are deduplicated.
+                if arg == "-Xlinker" {
+                    flags.push(directive);
+                    flags.extend(directives.next());
+                } else if arg.starts_with("-Wl,") || !flags.contains(&directive) {
+                    flags.push(directive);
+                }
+            }
+            // A library, whether it's named or passed by its path. The same library may arrive in
+            // both forms, so they're compared by the name they're linked by.
+            mut directive => {
+                let name = lib_name(&directive).map(str::to_owned);
+                let earlier = libs.iter().position(|existing| match name {
+                    Some(ref name) => lib_name(existing) == Some(name),
+                    None => *existing == directive,
+                });
+                if let Some(earlier) = earlier.map(|index| libs.remove(index)) {
+                    if let (LinkDirective::Lib(ref mut lib), LinkDirective::Lib(earlier)) =
+                        (&mut directive, earlier)
+                    {
+                        // An explicit kind wins over letting the linker decide.
+                        if lib.kind.is_none() && lib.name == earlier.name {
+                            lib.kind = earlier.kind;
+                            lib.modifiers = earlier.modifiers;
+                        }
+                    }
+                }
+                libs.push(directive);
+            }
+        }
+    }
+    // The sort is stable, so libraries the table doesn't know keep their order.
+    libs.sort_by_key(|lib| {
+        let position = lib_name(lib).and_then(dependencies::link_position);
+        (lib_rank(lib), position.map_or(0, |position| position + 1))
+    });
+
+    let mut plan = search_paths;
+    if group {
+        let (grouped, rest): (Vec<_>, Vec<_>) = libs.into_iter().partition(|lib| {
+            lib_rank(lib) < 2
+                && match *lib {
+                    LinkDirective::Lib(ref lib) => {
+                        lib.kind.is_none_or(|kind| kind == LinkKind::Static)
+                    }
+                    _ => false,
+                }
+        });
+        if !grouped.is_empty() {
+            plan.push(LinkDirective::Arg("-Wl,--start-group".to_owned()));
+            plan.extend(grouped.into_iter().flat_map(lib_args));
+            plan.push(LinkDirective::Arg("-Wl,--end-group".to_owned()));
+        }
+        // Frameworks can't be named with `-l`, and their order doesn't matter to Apple's linker.
+        plan.extend(rest.into_iter().flat_map(|lib| match lib {
+            LinkDirective::Lib(ref link_lib) if link_lib.kind == Some(LinkKind::Framework) => {
+                vec![lib]
+            }
+            lib => lib_args(lib),
+        }));
+    } else {
+        plan.extend(libs);
+    }
+    plan.extend(flags);
+    plan
+}
+
+// Folly's own libraries sort first, then other libraries, then system libraries.
+fn lib_rank(lib: &LinkDirective) -> u8 {
+    match lib_name(lib) {
+        Some(name) if name == "folly" || name.starts_with("folly_") => 0,
+        Some(name) if SYSTEM_LIBS.contains(&name) => 2,
+        _ => 1,
+    }
+}
+
+/// The name a library is linked by, or for one given by its file name or path, the name it would
+/// be linked by: `double-conversion` for `libdouble-conversion.so.3`.
+pub(crate) fn lib_name(lib: &LinkDirective) -> Option<&str> {
+    let path = match *lib {
+        LinkDirective::Lib(ref lib) if !lib.modifiers.contains(&LinkModifier::Verbatim(true)) => {
+            return Some(&lib.name)
+        }
+        LinkDirective::Lib(ref lib) => &lib.name,
+        LinkDirective::Arg(ref path) => path,
+        LinkDirective::SearchPath(_) => return None,
+    };
+    let file_name = Path::new(path).file_name()?.to_str()?;
+    let name = file_name.strip_prefix("lib").unwrap_or(file_name);
+    name.split('.').next()
+}
+
+// Turns a library into the linker arguments that link it, for when libraries are grouped.
+fn lib_args(lib: LinkDirective) -> Vec<LinkDirective> {
+    let lib = match lib {
+        LinkDirective::Lib(lib) => lib,
+        // Already a path.
+        _ => return vec![lib],
+    };
+    let arg = if lib.modifiers.contains(&LinkModifier::Verbatim(true)) {
+        format!("-l:{}", lib.name)
+    } else if lib.kind == Some(LinkKind::Static) {
+        format!("-l:lib{}.a", lib.name)
+    } else {
+        format!("-l{}", lib.name)
+    };
+    if lib.modifiers.contains(&LinkModifier::WholeArchive(true)) {
+        ["-Wl,--whole-archive", &arg, "-Wl,--no-whole-archive"]
+            .iter()
+            .map(|arg| LinkDirective::Arg((*arg).to_owned()))
+            .collect()
+    } else {
+        vec![LinkDirective::Arg(arg)]
+    }
+}
+
+/// Adds the `whole-archive` and `bundle` modifiers that the probe was configured with to the
+/// static libraries in `directives`. `statik` says how libraries without an explicit kind are
+/// linked.
+///
+/// Returns the names in `whole_archive` that aren't linked statically.
+pub(crate) fn apply_modifiers(
+    directives: &mut [LinkDirective],
+    whole_archive: &[String],
+    bundle: Option<bool>,
+    statik: bool,
+) -> Vec<String> {
+    let mut unmatched = whole_archive.to_vec();
+    for directive in directives {
+        let lib = match *directive {
+            LinkDirective::Lib(ref mut lib) => lib,
+            _ => continue,
+        };
+        if !lib.kind.map_or(statik, |kind| kind == LinkKind::Static) {
+            continue;
+        }
+        if whole_archive.contains(&lib.name) {
+            unmatched.retain(|name| *name != lib.name);
+            // Modifiers require an explicit kind.
+            lib.kind = Some(LinkKind::Static);
+            lib.set_modifier(LinkModifier::WholeArchive(true));
+        }
+        if let (Some(bundle), Some(LinkKind::Static)) = (bundle, lib.kind) {
+            lib.set_modifier(LinkModifier::Bundle(bundle));
+        }
+    }
+    unmatched
+}
+
+/// Gives an explicit kind to each library that the linker would otherwise pick a file for: static
+/// if `statik` is true, and shared otherwise. Without one, the linker prefers `libfoo.so` to
+/// `libfoo.a` in the same directory, whatever `pkg-config --static` said. `dirs` are the
+/// directories the linker searches.
+///
+/// System libraries are left to the linker, as are libraries that aren't in any of `dirs`. It's an
+/// error if a library only exists as the other kind, so `directives` should only contain libraries
+/// that are required.
+pub(crate) fn resolve_kinds(
+    directives: &mut [LinkDirective],
+    dirs: &[PathBuf],
+    statik: bool,
+) -> Result<(), FollyError> {
+    let (wanted, other) = if statik {
+        (LinkKind::Static, LinkKind::Dylib)
+    } else {
+        (LinkKind::Dylib, LinkKind::Static)
+    };
+    for directive in directives {
+        let lib = match *directive {
+            LinkDirective::Lib(ref mut lib) if lib.kind.is_none_or(|kind| kind == wanted) => lib,
+            _ => continue,
+        };
+        if SYSTEM_LIBS.contains(&lib.name.as_str())
+            || lib.modifiers.contains(&LinkModifier::Verbatim(true))
+        {
+            continue;
+        }
+        let find = |kind| {
+            dirs.iter()
+                .flat_map(|dir| file_names(&lib.name, kind).map(|file_name| dir.join(file_name)))
+                .find(|path| path.is_file())
+        };
+        if find(wanted).is_some() {
+            lib.kind = Some(wanted);
+        } else if let Some(found) = find(other) {
+            return Err(FollyError::LinkKindUnavailable {
+                name: lib.name.clone(),
+                statik,
+                found,
+            });
+        }
+    }
+    Ok(())
+}
+
+// The names of the files that `-l<name>` finds, for each kind.
+fn file_names(name: &str, kind: LinkKind) -> impl Iterator<Item = String> + '_ {
+    let suffixes: &[&str] = match kind {
+        LinkKind::Static => &[".a"],
+        _ => &[".so", ".dylib", ".tbd"],
+    };
+    suffixes
+        .iter()
+        .map(move |suffix| format!("lib{}{}", name, suffix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lib(name: &str) -> LinkDirective {
+        LinkDirective::Lib(LinkLib {
+            name: name.to_owned(),
+            kind: None,
+            modifiers: vec![],
+        })
+    }
+
+    fn arg(arg: &str) -> LinkDirective {
+        LinkDirective::Arg(arg.to_owned())
+    }
+
+    fn search_path(path: &str) -> LinkDirective {
+        LinkDirective::SearchPath(SearchPath::native(path))
+    }
+
+    #[test]
+    fn consolidate_orders_search_paths_libraries_and_flags() {
+        let plan = consolidate(
+            vec![
+                search_path("/opt/folly/lib"),
+                lib("pthread"),
+                arg("-Wl,--as-needed"),
+                lib("folly"),
+                search_path("/opt/folly/lib"),
+                lib("boost_context"),
+                arg("-pthread"),
+                lib("fmt"),
+                arg("-pthread"),
+            ],
+            false,
+        );
+        assert_eq!(
+            plan,
+            [
+                search_path("/opt/folly/lib"),
+                lib("folly"),
+                lib("boost_context"),
+                lib("fmt"),
+                lib("pthread"),
+                arg("-Wl,--as-needed"),
+                arg("-pthread"),
+            ]
+        );
+    }
+
+    #[test]
+    fn consolidate_keeps_the_last_position_of_a_duplicate() {
+        let plan = consolidate(
+            vec![lib("fmt"), lib("folly"), lib("boost_regex"), lib("fmt")],
+            false,
+        );
+        assert_eq!(plan, [lib("folly"), lib("boost_regex"), lib("fmt")]);
+
+        // The same library by its path and by its name.
+        let plan = consolidate(
+            vec![
+                arg("/usr/lib/x86_64-linux-gnu/libdouble-conversion.so.3"),
+                lib("folly"),
+                lib("boost_regex"),
+                lib("double-conversion"),
+                lib("fmt"),
+                arg("/opt/fmt/lib/libfmt.a"),
+            ],
+            false,
+        );
+        assert_eq!(
+            plan,
+            [
+                lib("folly"),
+                lib("boost_regex"),
+                arg("/opt/fmt/lib/libfmt.a"),
+                lib("double-conversion"),
+            ]
+        );
+    }
+
+    #[test]
+    fn consolidate_puts_known_dependencies_in_link_order() {
+        // Folly's CMake export lists `gflags` before `glog`, which depends on it.
+        let plan = consolidate(
+            vec![
+                lib("folly"),
+                lib("gflags"),
+                lib("glog"),
+                lib("ssl"),
+                arg("/usr/lib/libdouble-conversion.so.3"),
+                lib("boost_context"),
+                lib("crypto"),
+                lib("dl"),
+            ],
+            false,
+        );
+        assert_eq!(
+            plan,
+            [
+                lib("folly"),
+                lib("boost_context"),
+                lib("glog"),
+                lib("gflags"),
+                arg("/usr/lib/libdouble-conversion.so.3"),
+                lib("ssl"),
+                lib("crypto"),
+                lib("dl"),
+            ]
+        );
+    }
+
+    #[test]
+    fn consolidate_groups_static_libraries() {
+        let plan = consolidate(
+            vec![
+                lib("folly"),
+                LinkDirective::Lib(LinkLib {
+                    name: "glog".to_owned(),
+                    kind: Some(LinkKind::Static),
+                    modifiers: vec![LinkModifier::WholeArchive(true)],
+                }),
+                LinkDirective::Lib(LinkLib {
+                    name: "ssl".to_owned(),
+                    kind: Some(LinkKind::Dylib),
+                    modifiers: vec![],
+                }),
+                lib("pthread"),
+            ],
+            true,
+        );
+        assert_eq!(
+            plan,
+            [
+                arg("-Wl,--start-group"),
+                arg("-lfolly"),
+                arg("-Wl,--whole-archive"),
+                arg("-l:libglog.a"),
+                arg("-Wl,--no-whole-archive"),
+                arg("-Wl,--end-group"),
+                arg("-lssl"),
+                arg("-lpthread"),
+            ]
+        );
+    }
+
+    fn lib_with(name: &str, kind: Option<LinkKind>, modifiers: &[LinkModifier]) -> LinkDirective {
+        LinkDirective::Lib(LinkLib {
+            name: name.to_owned(),
+            kind,
+            modifiers: modifiers.to_vec(),
+        })
+    }
+
+    #[test]
+    fn apply_modifiers_marks_static_libraries() {
+        let mut directives = vec![
+            lib("folly"),
+            lib_with("glog", Some(LinkKind::Static), &[]),
+            lib_with("ssl", Some(LinkKind::Dylib), &[]),
+            lib_with("Security", Some(LinkKind::Framework), &[]),
+            arg("/opt/lib/libgflags.a"),
+        ];
+        let unmatched = apply_modifiers(
+            &mut directives,
+            &["folly".to_owned(), "ssl".to_owned(), "gflags".to_owned()],
+            Some(false),
+            true,
+        );
+        assert_eq!(unmatched, ["ssl", "gflags"]);
+        assert_eq!(
+            directives,
+            [
+                lib_with(
+                    "folly",
+                    Some(LinkKind::Static),
+                    &[
+                        LinkModifier::WholeArchive(true),
+                        LinkModifier::Bundle(false)
+                    ]
+                ),
+                lib_with(
+                    "glog",
+                    Some(LinkKind::Static),
+                    &[LinkModifier::Bundle(false)]
+                ),
+                lib_with("ssl", Some(LinkKind::Dylib), &[]),
+                lib_with("Security", Some(LinkKind::Framework), &[]),
+                arg("/opt/lib/libgflags.a"),
+            ]
+        );
+    }
+
+    #[test]
+    fn apply_modifiers_leaves_kindless_libraries_alone_when_linking_dynamically() {
+        let mut directives = vec![lib("folly"), lib_with("glog", Some(LinkKind::Static), &[])];
+        let unmatched = apply_modifiers(&mut directives, &["folly".to_owned()], Some(true), false);
+        assert_eq!(unmatched, ["folly"]);
+        assert_eq!(
+            directives,
+            [
+                lib("folly"),
+                lib_with(
+                    "glog",
+                    Some(LinkKind::Static),
+                    &[LinkModifier::Bundle(true)]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn apply_modifiers_without_options_changes_nothing() {
+        let mut directives = vec![lib("folly"), lib_with("glog", Some(LinkKind::Static), &[])];
+        let expected = directives.clone();
+        assert!(apply_modifiers(&mut directives, &[], None, true).is_empty());
+        assert_eq!(directives, expected);
+    }
+
+    #[test]
+    fn classify_library_path_recognizes_library_files() {
+        let named = |dir: &str, name: &str, kind| LibraryPath::Named {
+            dir: PathBuf::from(dir),
+            name: name.to_owned(),
+            kind,
+        };
+        let cases = [
+            (
+                "/opt/lib/libfoo.a",
+                named("/opt/lib", "foo", Some(LinkKind::Static)),
+            ),
+            (
+                "/opt/lib/libfoo.so",
+                named("/opt/lib", "foo", Some(LinkKind::Dylib)),
+            ),
+            (
+                "/opt/lib/libfoo.dylib",
+                named("/opt/lib", "foo", Some(LinkKind::Dylib)),
+            ),
+            (
+                "/sdk/usr/lib/libc++.tbd",
+                named("/sdk/usr/lib", "c++", Some(LinkKind::Dylib)),
+            ),
+            (
+                "C:/folly/lib/folly.lib",
+                named("C:/folly/lib", "folly", None),
+            ),
+            (
+                "C:/folly/lib/libfolly.lib",
+                named("C:/folly/lib", "libfolly", None),
+            ),
+            (
+                "relative/libfoo.a",
+                named("relative", "foo", Some(LinkKind::Static)),
+            ),
+            ("/usr/lib/libdouble-conversion.so.3", LibraryPath::Verbatim),
+            ("/usr/lib/libfoo.so.1.2.3", LibraryPath::Verbatim),
+            ("/opt/lib/foo.a", LibraryPath::Verbatim),
+            ("/opt/lib/lib.a", LibraryPath::Verbatim),
+            ("/opt/lib/foo.so", LibraryPath::Verbatim),
+            ("/opt/lib/.lib", LibraryPath::Unrecognized),
+            ("/usr/lib/libfoo.so.1a", LibraryPath::Unrecognized),
+            ("/usr/lib/libfoo.so.", LibraryPath::Unrecognized),
+            ("/usr/lib/libfoo.so.1..2", LibraryPath::Unrecognized),
+            ("/opt/include/foo.h", LibraryPath::Unrecognized),
+            ("/", LibraryPath::Unrecognized),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(classify_library_path(Path::new(path)), expected, "{}", path);
+        }
+    }
+}
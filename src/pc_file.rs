@@ -0,0 +1,497 @@
+// find-folly/src/pc_file.rs
+//
+//! A minimal reader for `.pc` files, used when no `pkg-config` binary is available.
+//!
+//! Minimal build containers often ship `libfolly.pc` without `pkg-config` or `pkgconf`. This
+//! module implements just enough of `pkg-config` to answer the queries this crate makes: variable
+//! expansion, `Requires` and `Requires.private` recursion, `Libs.private` in static mode, and the
+//! `PKG_CONFIG_PATH`, `PKG_CONFIG_LIBDIR`, and `PKG_CONFIG_SYSROOT_DIR` environment variables.
+
+use crate::query::{self, Query};
+use crate::FollyError;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Answers `pkg-config` queries by reading `.pc` files directly.
+pub(crate) struct PcFileReader {
+    search_dirs: Vec<PathBuf>,
+    system_lib_dirs: Vec<PathBuf>,
+    sysroot: Option<PathBuf>,
+    statik: bool,
+}
+
+// A parsed `.pc` file.
+struct PcFile {
+    variables: HashMap<String, String>,
+    fields: HashMap<String, String>,
+}
+
+// Directories that `pkg-config` strips from `-I` and `-L` flags by default, because the compiler
+// and linker already search them. Debian's `pkg-config` also strips the multiarch library
+// directories.
+const SYSTEM_INCLUDE_DIRS: &[&str] = &["/usr/include"];
+const SYSTEM_LIB_DIRS: &[&str] = &["/usr/lib", "/lib", "/usr/lib64", "/lib64"];
+
+impl PcFileReader {
+    /// Creates a reader that searches `extra_search_dirs`, followed by the same directories
+    /// `pkg-config` would.
+    pub(crate) fn new(statik: bool, extra_search_dirs: &[PathBuf]) -> Self {
+        let mut search_dirs = extra_search_dirs.to_vec();
+        if let Some(path) = env::var_os("PKG_CONFIG_PATH") {
+            search_dirs.extend(env::split_paths(&path));
+        }
+        match env::var_os("PKG_CONFIG_LIBDIR") {
+            Some(libdir) => search_dirs.extend(env::split_paths(&libdir)),
+            None => search_dirs.extend(default_search_dirs()),
+        }
+        let mut system_lib_dirs: Vec<_> = SYSTEM_LIB_DIRS.iter().map(PathBuf::from).collect();
+        if let Some(multiarch) = multiarch_triple() {
+            system_lib_dirs.push(Path::new("/usr/lib").join(&multiarch));
+            system_lib_dirs.push(Path::new("/lib").join(&multiarch));
+        }
+        Self {
+            search_dirs,
+            system_lib_dirs,
+            sysroot: env::var_os("PKG_CONFIG_SYSROOT_DIR").map(PathBuf::from),
+            statik,
+        }
+    }
+
+    /// Answers `query` for `package`, returning the same arguments `pkg-config` would print.
+    pub(crate) fn query(&self, package: &str, query: Query) -> Result<Vec<OsString>, FollyError> {
+        let mut visited = HashSet::new();
+        let mut args = vec![];
+        self.collect(package, query, &mut visited, &mut args)?;
+        Ok(args)
+    }
+
+    /// Returns the `Version` field of `package`'s `.pc` file.
+    pub(crate) fn modversion(&self, package: &str) -> Result<String, FollyError> {
+        Ok(self.load(package)?.field("Version"))
+    }
+
+    /// Returns the paths of the `.pc` files for `package` and everything it requires.
+    pub(crate) fn pc_files(&self, package: &str) -> Vec<PathBuf> {
+        let mut visited = HashSet::new();
+        let mut paths = vec![];
+        let mut pending = vec![package.to_owned()];
+        while let Some(package) = pending.pop() {
+            if !visited.insert(package.clone()) {
+                continue;
+            }
+            let path = match self.find(&package) {
+                Some(path) => path,
+                None => continue,
+            };
+            if let Ok(pc_file) = self.load(&package) {
+                pending.extend(parse_requires(&pc_file.field("Requires")));
+                pending.extend(parse_requires(&pc_file.field("Requires.private")));
+            }
+            paths.push(path);
+        }
+        paths
+    }
+
+    fn collect(
+        &self,
+        package: &str,
+        query: Query,
+        visited: &mut HashSet<String>,
+        args: &mut Vec<OsString>,
+    ) -> Result<(), FollyError> {
+        if !visited.insert(package.to_owned()) {
+            return Ok(());
+        }
+        let pc_file = self.load(package)?;
+
+        let mut fields = match query {
+            Query::Libs => vec!["Libs"],
+            Query::Cflags => vec!["Cflags"],
+        };
+        if self.statik && query == Query::Libs {
+            fields.push("Libs.private");
+        }
+        for field in fields {
+            for arg in query::tokenize(pc_file.field(field).as_bytes()) {
+                args.extend(self.relocate(arg));
+            }
+        }
+
+        // `pkg-config` always includes the compiler flags of private requirements, but only
+        // includes their libraries when linking statically.
+        let mut requires = parse_requires(&pc_file.field("Requires"));
+        if self.statik || query == Query::Cflags {
+            requires.extend(parse_requires(&pc_file.field("Requires.private")));
+        }
+        for required_package in requires {
+            self.collect(&required_package, query, visited, args)?;
+        }
+        Ok(())
+    }
+
+    fn find(&self, package: &str) -> Option<PathBuf> {
+        let file_name = format!("{}.pc", package);
+        self.search_dirs
+            .iter()
+            .map(|dir| dir.join(&file_name))
+            .find(|path| path.is_file())
+    }
+
+    fn load(&self, package: &str) -> Result<PcFile, FollyError> {
+        let path = match self.find(package) {
+            Some(path) => path,
+            None => {
+                return Err(FollyError::PcFileNotFound {
+                    package: package.to_owned(),
+                    search_dirs: self.search_dirs.clone(),
+                })
+            }
+        };
+        let contents =
+            fs::read(&path).map_err(|error| FollyError::ReadFile(path.clone(), error))?;
+        Ok(PcFile::parse(
+            &String::from_utf8_lossy(&contents),
+            &path,
+            self.sysroot.as_deref(),
+        ))
+    }
+
+    // Applies `PKG_CONFIG_SYSROOT_DIR` to `-I` and `-L` flags, and drops the system directories
+    // that `pkg-config` would drop.
+    fn relocate(&self, arg: OsString) -> Option<OsString> {
+        let system_include_dirs: Vec<_> = SYSTEM_INCLUDE_DIRS.iter().map(PathBuf::from).collect();
+        for (flag, system_dirs) in [("-I", &system_include_dirs), ("-L", &self.system_lib_dirs)] {
+            let path = match query::strip_flag(&arg, flag) {
+                Some(path) => PathBuf::from(path),
+                None => continue,
+            };
+            if system_dirs.contains(&path) {
+                return None;
+            }
+            let sysroot = match self.sysroot {
+                Some(ref sysroot) if path.is_absolute() && !path.starts_with(sysroot) => sysroot,
+                _ => return Some(arg),
+            };
+            let mut relocated = OsString::from(flag);
+            relocated.push(sysroot.join(path.strip_prefix("/").unwrap_or(&path)));
+            return Some(relocated);
+        }
+        Some(arg)
+    }
+}
+
+impl PcFile {
+    fn parse(contents: &str, path: &Path, sysroot: Option<&Path>) -> PcFile {
+        let mut variables = HashMap::new();
+        if let Some(dir) = path.parent() {
+            variables.insert("pcfiledir".to_owned(), dir.to_string_lossy().into_owned());
+        }
+        variables.insert(
+            "pc_sysrootdir".to_owned(),
+            sysroot.map_or("/".to_owned(), |sysroot| {
+                sysroot.to_string_lossy().into_owned()
+            }),
+        );
+
+        let mut pc_file = PcFile {
+            variables,
+            fields: HashMap::new(),
+        };
+        for line in logical_lines(contents) {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            // Whichever of `=` and `:` comes first decides whether this is a variable definition
+            // or a field.
+            let separator = match line.find(['=', ':']) {
+                Some(separator) => separator,
+                None => continue,
+            };
+            let (name, value) = (line[..separator].trim(), line[separator + 1..].trim());
+            let value = pc_file.expand(value);
+            if line.as_bytes()[separator] == b'=' {
+                pc_file.variables.insert(name.to_owned(), value);
+            } else {
+                pc_file.fields.insert(name.to_owned(), value);
+            }
+        }
+        pc_file
+    }
+
+    fn field(&self, name: &str) -> String {
+        self.fields.get(name).cloned().unwrap_or_default()
+    }
+
+    // Expands `${variable}` references. Variables are expanded as they're defined, so later
+    // definitions can't affect earlier ones, just as in `pkg-config`.
+    fn expand(&self, value: &str) -> String {
+        let mut expanded = String::new();
+        let mut rest = value;
+        while let Some(dollar) = rest.find('$') {
+            expanded.push_str(&rest[..dollar]);
+            rest = &rest[dollar..];
+            if let Some(after) = rest.strip_prefix("$$") {
+                expanded.push('$');
+                rest = after;
+            } else if let Some(close) = rest.strip_prefix("${").and_then(|after| after.find('}')) {
+                let name = &rest[2..close + 2];
+                if let Some(value) = self.variables.get(name) {
+                    expanded.push_str(value);
+                }
+                rest = &rest[close + 3..];
+            } else {
+                expanded.push('$');
+                rest = &rest[1..];
+            }
+        }
+        expanded.push_str(rest);
+        expanded
+    }
+}
+
+// Joins lines ending in a backslash and strips `#` comments.
+fn logical_lines(contents: &str) -> Vec<String> {
+    let mut lines = vec![];
+    let mut current = String::new();
+    for line in contents.lines() {
+        let line = strip_comment(line);
+        match line.strip_suffix('\\') {
+            Some(continued) => current.push_str(continued),
+            None => {
+                current.push_str(&line);
+                lines.push(std::mem::take(&mut current));
+            }
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+fn strip_comment(line: &str) -> String {
+    let mut stripped = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('#') => stripped.push('#'),
+                Some(next) => {
+                    stripped.push('\\');
+                    stripped.push(next);
+                }
+                None => stripped.push('\\'),
+            },
+            '#' => break,
+            _ => stripped.push(c),
+        }
+    }
+    stripped
+}
+
+// Parses a `Requires` field such as `fmt >= 8.0, gflags glog`, discarding version constraints.
+fn parse_requires(requires: &str) -> Vec<String> {
+    let mut packages = vec![];
+    let mut words = requires
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|word| !word.is_empty());
+    while let Some(word) = words.next() {
+        if matches!(word, "=" | "!=" | "<" | "<=" | ">" | ">=") {
+            words.next();
+            continue;
+        }
+        packages.push(word.to_owned());
+    }
+    packages
+}
+
+// The directories `pkg-config` searches when `PKG_CONFIG_LIBDIR` isn't set. The exact list is
+// decided when `pkg-config` is built, so this is a best guess covering the common layouts.
+fn default_search_dirs() -> Vec<PathBuf> {
+    let mut prefixes = vec!["/usr/local", "/usr"];
+    if cfg!(target_os = "macos") {
+        prefixes.insert(0, "/opt/homebrew");
+    }
+    let mut lib_dirs = vec!["lib".to_owned(), "lib64".to_owned()];
+    if let Some(multiarch) = multiarch_triple() {
+        lib_dirs.insert(0, format!("lib/{}", multiarch));
+    }
+
+    let mut dirs = vec![];
+    for prefix in prefixes {
+        for lib_dir in &lib_dirs {
+            dirs.push(Path::new(prefix).join(lib_dir).join("pkgconfig"));
+        }
+        dirs.push(Path::new(prefix).join("share/pkgconfig"));
+    }
+    dirs
+}
+
+/// Returns the Debian-style multiarch triple for the target, such as `x86_64-linux-gnu`.
+pub(crate) fn multiarch_triple() -> Option<String> {
+    let target = env::var("TARGET").ok()?;
+    let mut parts = target.split('-');
+    let arch = match parts.next()? {
+        "i586" | "i686" => "i386",
+        arch if arch.starts_with("armv7") => "arm",
+        arch => arch,
+    };
+    let rest: Vec<_> = parts.filter(|part| *part != "unknown").collect();
+    if rest.first() != Some(&"linux") {
+        return None;
+    }
+    Some(format!("{}-{}", arch, rest.join("-")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> PcFile {
+        PcFile::parse(
+            contents,
+            Path::new("/opt/folly/lib/pkgconfig/libfolly.pc"),
+            None,
+        )
+    }
+
+    fn reader(sysroot: Option<&str>) -> PcFileReader {
+        PcFileReader {
+            search_dirs: vec![],
+            system_lib_dirs: ["/usr/lib", "/usr/lib64", "/usr/lib/x86_64-linux-gnu"]
+                .iter()
+                .map(PathBuf::from)
+                .collect(),
+            sysroot: sysroot.map(PathBuf::from),
+            statik: true,
+        }
+    }
+
+    fn relocate(reader: &PcFileReader, arg: &str) -> Option<String> {
+        reader
+            .relocate(arg.into())
+            .map(|arg| arg.into_string().unwrap())
+    }
+
+    #[test]
+    fn parse_expands_variables_and_fields() {
+        let pc_file = parse(
+            "prefix=/opt/folly\n\
+             exec_prefix=${prefix}\n\
+             libdir=${exec_prefix}/lib\n\
+             \n\
+             Name: libfolly\n\
+             Version: 2023.05.22.00\n\
+             Libs: -L${libdir} -lfolly\n",
+        );
+        assert_eq!(pc_file.variables["libdir"], "/opt/folly/lib");
+        assert_eq!(pc_file.field("Version"), "2023.05.22.00");
+        assert_eq!(pc_file.field("Libs"), "-L/opt/folly/lib -lfolly");
+        assert_eq!(pc_file.field("Cflags"), "");
+    }
+
+    #[test]
+    fn parse_defines_pcfiledir_and_pc_sysrootdir() {
+        let pc_file = parse("Cflags: -I${pcfiledir}/../../include ${pc_sysrootdir}");
+        assert_eq!(
+            pc_file.field("Cflags"),
+            "-I/opt/folly/lib/pkgconfig/../../include /"
+        );
+        let pc_file = PcFile::parse(
+            "Cflags: -I${pc_sysrootdir}usr/include",
+            Path::new("libfolly.pc"),
+            Some(Path::new("/sysroot/")),
+        );
+        assert_eq!(pc_file.field("Cflags"), "-I/sysroot/usr/include");
+    }
+
+    #[test]
+    fn parse_uses_whichever_separator_comes_first() {
+        let pc_file = parse("url=https://example.com\nDescription: a=b");
+        assert_eq!(pc_file.variables["url"], "https://example.com");
+        assert_eq!(pc_file.field("Description"), "a=b");
+    }
+
+    #[test]
+    fn parse_strips_comments_and_joins_continued_lines() {
+        let pc_file = parse(
+            "# A comment\n\
+             prefix=/opt/folly # trailing comment\n\
+             Libs: -L${prefix}/lib \\\n  -lfolly\n\
+             Description: Issue \\#42\n",
+        );
+        assert_eq!(pc_file.variables["prefix"], "/opt/folly");
+        assert_eq!(pc_file.field("Libs"), "-L/opt/folly/lib   -lfolly");
+        assert_eq!(pc_file.field("Description"), "Issue #42");
+    }
+
+    #[test]
+    fn expand_uses_definitions_seen_so_far() {
+        let pc_file = parse("libdir=${prefix}/lib\nprefix=/opt/folly\nLibs: -L${libdir}");
+        assert_eq!(pc_file.field("Libs"), "-L/lib");
+    }
+
+    #[test]
+    fn expand_handles_dollar_signs() {
+        let pc_file = parse("name=folly");
+        assert_eq!(pc_file.expand("$${name}"), "${name}");
+        assert_eq!(pc_file.expand("a$b ${name}"), "a$b folly");
+        assert_eq!(pc_file.expand("${undefined}x"), "x");
+        assert_eq!(pc_file.expand("${unterminated"), "${unterminated");
+    }
+
+    #[test]
+    fn parse_requires_discards_version_constraints() {
+        assert_eq!(
+            parse_requires("fmt >= 8.0, gflags glog = 0.6,libevent"),
+            ["fmt", "gflags", "glog", "libevent"]
+        );
+        assert_eq!(
+            parse_requires("a != 1 b < 2 c <= 3 d > 4"),
+            ["a", "b", "c", "d"]
+        );
+        assert!(parse_requires("").is_empty());
+    }
+
+    #[test]
+    fn relocate_drops_system_dirs() {
+        let reader = reader(None);
+        assert_eq!(relocate(&reader, "-I/usr/include"), None);
+        assert_eq!(relocate(&reader, "-L/usr/lib64"), None);
+        assert_eq!(relocate(&reader, "-L/usr/lib/x86_64-linux-gnu"), None);
+        assert_eq!(
+            relocate(&reader, "-L/usr/local/lib").as_deref(),
+            Some("-L/usr/local/lib")
+        );
+        assert_eq!(
+            relocate(&reader, "-I/usr/include/boost").as_deref(),
+            Some("-I/usr/include/boost")
+        );
+        assert_eq!(relocate(&reader, "-lfolly").as_deref(), Some("-lfolly"));
+    }
+
+    #[test]
+    fn relocate_applies_sysroot() {
+        let reader = reader(Some("/sysroot"));
+        assert_eq!(
+            relocate(&reader, "-I/opt/folly/include").as_deref(),
+            Some("-I/sysroot/opt/folly/include")
+        );
+        assert_eq!(
+            relocate(&reader, "-L/sysroot/opt/folly/lib").as_deref(),
+            Some("-L/sysroot/opt/folly/lib")
+        );
+        assert_eq!(
+            relocate(&reader, "-Irelative").as_deref(),
+            Some("-Irelative")
+        );
+        assert_eq!(
+            relocate(&reader, "-DFOLLY=/opt").as_deref(),
+            Some("-DFOLLY=/opt")
+        );
+    }
+}
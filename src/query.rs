@@ -0,0 +1,189 @@
+// find-folly/src/query.rs
+//
+//! Running the `pkg-config` binary and tokenizing its output.
+//!
+//! Paths reported by `pkg-config` aren't guaranteed to be UTF-8, so everything here works in terms
+//! of `OsString` rather than `String`. If the binary isn't installed, queries are answered by
+//! reading `.pc` files directly instead.
+
+use crate::pc_file::PcFileReader;
+use crate::FollyError;
+use shlex::bytes::Shlex;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// The questions this crate asks `pkg-config`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Query {
+    /// `--libs`.
+    Libs,
+    /// `--cflags`.
+    Cflags,
+}
+
+/// The environment variables that affect what `pkg-config` reports.
+pub(crate) const ENV_VARS: &[&str] = &[
+    "PKG_CONFIG",
+    "PKG_CONFIG_PATH",
+    "PKG_CONFIG_LIBDIR",
+    "PKG_CONFIG_SYSROOT_DIR",
+];
+
+/// Runs `pkg-config`, or whatever binary the `PKG_CONFIG` environment variable names.
+pub(crate) struct PkgConfig {
+    program: OsString,
+    statik: bool,
+    extra_search_dirs: Vec<PathBuf>,
+}
+
+impl PkgConfig {
+    pub(crate) fn new(statik: bool) -> Self {
+        Self {
+            program: env::var_os("PKG_CONFIG").unwrap_or_else(|| OsString::from("pkg-config")),
+            statik,
+            extra_search_dirs: vec![],
+        }
+    }
+
+    /// Searches `dirs` for `.pc` files before anything in `PKG_CONFIG_PATH`.
+    pub(crate) fn search_dirs_first(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.extra_search_dirs = dirs;
+        self
+    }
+
+    /// Runs `pkg-config <query> <package>` and returns the arguments it printed.
+    ///
+    /// Fails if the binary exits unsuccessfully, in which case the error includes whatever
+    /// `pkg-config` printed to standard error. If the binary doesn't exist at all, the `.pc` files
+    /// are read directly.
+    pub(crate) fn query(&self, package: &str, query: Query) -> Result<Vec<OsString>, FollyError> {
+        let pkg_config_path = self.pkg_config_path();
+        let mut command = self.command(pkg_config_path.as_ref());
+        if self.statik {
+            command.arg("--static");
+        }
+        command.arg(match query {
+            Query::Libs => "--libs",
+            Query::Cflags => "--cflags",
+        });
+        let output = match command.arg(package).output() {
+            Ok(output) => output,
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                return PcFileReader::new(self.statik, &self.extra_search_dirs)
+                    .query(package, query);
+            }
+            Err(error) => return Err(FollyError::PkgConfigSpawn(error)),
+        };
+        if !output.status.success() {
+            return Err(FollyError::PkgConfig {
+                package: package.to_owned(),
+                code: output.status.code(),
+                stderr: String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+                pkg_config_path,
+            });
+        }
+        Ok(tokenize(&output.stdout))
+    }
+
+    /// Runs `pkg-config --modversion <package>`.
+    pub(crate) fn modversion(&self, package: &str) -> Result<String, FollyError> {
+        let pkg_config_path = self.pkg_config_path();
+        let output = match self
+            .command(pkg_config_path.as_ref())
+            .arg("--modversion")
+            .arg(package)
+            .output()
+        {
+            Ok(output) => output,
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                return PcFileReader::new(self.statik, &self.extra_search_dirs).modversion(package);
+            }
+            Err(error) => return Err(FollyError::PkgConfigSpawn(error)),
+        };
+        if !output.status.success() {
+            return Err(FollyError::PkgConfig {
+                package: package.to_owned(),
+                code: output.status.code(),
+                stderr: String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+                pkg_config_path,
+            });
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+    }
+
+    /// Returns the `.pc` files that describe `package`, so that changes to them can be tracked.
+    ///
+    /// When the `pkg-config` binary is available, only the package's own file is returned, since
+    /// the binary doesn't report which files it read for the package's requirements.
+    pub(crate) fn pc_files(&self, package: &str) -> Vec<PathBuf> {
+        let output = self
+            .command(self.pkg_config_path().as_ref())
+            .arg("--variable=pcfiledir")
+            .arg(package)
+            .output();
+        match output {
+            Ok(output) if output.status.success() => tokenize(&output.stdout)
+                .into_iter()
+                .map(|dir| PathBuf::from(dir).join(format!("{}.pc", package)))
+                .collect(),
+            Ok(_) => vec![],
+            Err(_) => PcFileReader::new(self.statik, &self.extra_search_dirs).pc_files(package),
+        }
+    }
+
+    fn command(&self, pkg_config_path: Option<&OsString>) -> Command {
+        let mut command = Command::new(&self.program);
+        if let Some(pkg_config_path) = pkg_config_path {
+            command.env("PKG_CONFIG_PATH", pkg_config_path);
+        }
+        command
+    }
+
+    // The value of `PKG_CONFIG_PATH` to run `pkg-config` with.
+    fn pkg_config_path(&self) -> Option<OsString> {
+        let existing = env::var_os("PKG_CONFIG_PATH");
+        if self.extra_search_dirs.is_empty() {
+            return existing;
+        }
+        let mut dirs = self.extra_search_dirs.clone();
+        dirs.extend(existing.iter().flat_map(env::split_paths));
+        env::join_paths(dirs).ok()
+    }
+}
+
+/// Splits `pkg-config` output into arguments, honoring shell quoting.
+pub(crate) fn tokenize(output: &[u8]) -> Vec<OsString> {
+    Shlex::new(output).map(bytes_to_os_string).collect()
+}
+
+/// If `arg` starts with the ASCII flag `flag`, returns the rest of the argument.
+pub(crate) fn strip_flag(arg: &OsStr, flag: &str) -> Option<OsString> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        arg.as_bytes()
+            .strip_prefix(flag.as_bytes())
+            .map(|rest| OsStr::from_bytes(rest).to_owned())
+    }
+    #[cfg(not(unix))]
+    {
+        arg.to_str()
+            .and_then(|arg| arg.strip_prefix(flag))
+            .map(OsString::from)
+    }
+}
+
+fn bytes_to_os_string(bytes: Vec<u8>) -> OsString {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        OsString::from_vec(bytes)
+    }
+    #[cfg(not(unix))]
+    {
+        OsString::from(String::from_utf8_lossy(&bytes).into_owned())
+    }
+}
@@ -0,0 +1,282 @@
+// find-folly/src/sysroot.rs
+//
+//! Recognizing the sysroot that Folly's headers and libraries belong to.
+//!
+//! A sysroot is a directory that stands in for `/` during the build, such as a macOS SDK or a
+//! Linux cross-compilation root like `/opt/aarch64-sysroot`. Once the compiler is told about it,
+//! with `-isysroot` for an Apple SDK and `--sysroot` otherwise, it searches the sysroot's own
+//! header and library directories by itself. Passing the sysroot's `usr/include` with `-I` instead
+//! breaks the C++ standard library's `#include_next` directives.
+//!
+//! The functions here only look at paths and flags, never at the environment or the file system,
+//! so they behave the same on every host.
+
+use std::path::{Path, PathBuf};
+
+/// The environment variables that may name the sysroot.
+pub(crate) const ENV_VARS: &[&str] = &["SDKROOT", "PKG_CONFIG_SYSROOT_DIR"];
+
+// The header directories under a sysroot that the compiler searches once it knows the sysroot.
+const INCLUDE_DIRS: &[&str] = &["usr/include", "usr/local/include"];
+
+/// Works out the sysroot from, in order of preference: a `--sysroot` or `-isysroot` flag in
+/// `cflags`, the `usr/include` directory of an Apple SDK among `include_paths`,
+/// `PKG_CONFIG_SYSROOT_DIR`, and `SDKROOT`.
+pub(crate) fn detect(
+    cflags: &[String],
+    include_paths: &[PathBuf],
+    pkg_config_sysroot_dir: Option<&Path>,
+    sdkroot: Option<&Path>,
+) -> Option<PathBuf> {
+    // An empty sysroot, or `/`, is the same as none.
+    let is_sysroot = |dir: &&Path| !dir.as_os_str().is_empty() && *dir != Path::new("/");
+    from_cflags(cflags)
+        .or_else(|| {
+            include_paths
+                .iter()
+                .find_map(|path| sdk_of_include_path(path))
+                .map(Path::to_owned)
+        })
+        .or_else(|| {
+            pkg_config_sysroot_dir
+                .filter(is_sysroot)
+                .map(Path::to_owned)
+        })
+        .or_else(|| sdkroot.filter(is_sysroot).map(Path::to_owned))
+}
+
+/// Returns the sysroot named by `--sysroot=<dir>`, `--sysroot <dir>`, `-isysroot <dir>`, or
+/// `-isysroot<dir>` in `cflags`. As with the compiler, the last one wins.
+pub(crate) fn from_cflags(cflags: &[String]) -> Option<PathBuf> {
+    let mut sysroot = None;
+    let mut args = cflags.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--sysroot" || arg == "-isysroot" {
+            sysroot = args.next().map(PathBuf::from).or(sysroot);
+        } else if let Some(dir) = arg
+            .strip_prefix("--sysroot=")
+            .or_else(|| arg.strip_prefix("-isysroot"))
+        {
+            sysroot = Some(PathBuf::from(dir));
+        }
+    }
+    sysroot
+}
+
+/// If `path` is the `usr/include` directory of an Apple SDK, returns the SDK. This recognizes both
+/// `/Library/Developer/CommandLineTools/SDKs/MacOSX13.sdk/usr/include` and the SDKs inside
+/// `Xcode.app`, such as
+/// `/Applications/Xcode.app/Contents/Developer/Platforms/MacOSX.platform/Developer/SDKs/MacOSX.sdk/usr/include`.
+pub(crate) fn sdk_of_include_path(path: &Path) -> Option<&Path> {
+    if !path.ends_with("usr/include") {
+        return None;
+    }
+    let sdk = path.parent()?.parent()?;
+    if is_apple_sdk(sdk) {
+        Some(sdk)
+    } else {
+        None
+    }
+}
+
+/// Returns true if `sysroot` is an Apple SDK, which is selected with `-isysroot` rather than
+/// `--sysroot`.
+pub(crate) fn is_apple_sdk(sysroot: &Path) -> bool {
+    sysroot
+        .extension()
+        .is_some_and(|extension| extension == "sdk")
+}
+
+/// Returns true if the compiler searches `path` for headers by itself once it's told about
+/// `sysroot`.
+pub(crate) fn is_default_include_dir(path: &Path, sysroot: &Path) -> bool {
+    path.strip_prefix(sysroot)
+        .is_ok_and(|relative| INCLUDE_DIRS.iter().any(|dir| relative == Path::new(dir)))
+}
+
+/// Returns the compiler flags that select `sysroot`.
+pub(crate) fn cflags(sysroot: &Path) -> Vec<String> {
+    if is_apple_sdk(sysroot) {
+        vec![
+            "-isysroot".to_owned(),
+            sysroot.to_string_lossy().into_owned(),
+        ]
+    } else {
+        vec![format!("--sysroot={}", sysroot.display())]
+    }
+}
+
+/// Returns the linker argument that selects `sysroot`, if one is needed. rustc already tells
+/// Apple's linker which SDK to use.
+pub(crate) fn link_arg(sysroot: &Path) -> Option<String> {
+    if is_apple_sdk(sysroot) {
+        None
+    } else {
+        Some(format!("--sysroot={}", sysroot.display()))
+    }
+}
+
+/// Returns where `path`, an absolute path on the target, is found on the host: under `sysroot` if
+/// there is one.
+pub(crate) fn under(sysroot: Option<&Path>, path: &Path) -> PathBuf {
+    match sysroot {
+        Some(sysroot) => sysroot.join(path.strip_prefix("/").unwrap_or(path)),
+        None => path.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMMAND_LINE_TOOLS_SDK: &str = "/Library/Developer/CommandLineTools/SDKs/MacOSX13.sdk";
+    const XCODE_SDK: &str = "/Applications/Xcode.app/Contents/Developer/Platforms/\
+        MacOSX.platform/Developer/SDKs/MacOSX.sdk";
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| (*arg).to_owned()).collect()
+    }
+
+    fn path(path: &str) -> Option<PathBuf> {
+        Some(PathBuf::from(path))
+    }
+
+    #[test]
+    fn detect_prefers_cflags_then_include_paths_then_the_environment() {
+        let cflags = strings(&["--sysroot=/opt/flag-sysroot"]);
+        let include_paths = [Path::new(XCODE_SDK).join("usr/include")];
+        let pkg_config_sysroot_dir = Some(Path::new("/opt/pkg-config-sysroot"));
+        let sdkroot = Some(Path::new("/opt/sdkroot"));
+        assert_eq!(
+            detect(&cflags, &include_paths, pkg_config_sysroot_dir, sdkroot),
+            path("/opt/flag-sysroot")
+        );
+        assert_eq!(
+            detect(&[], &include_paths, pkg_config_sysroot_dir, sdkroot),
+            path(XCODE_SDK)
+        );
+        assert_eq!(
+            detect(&[], &[], pkg_config_sysroot_dir, sdkroot),
+            path("/opt/pkg-config-sysroot")
+        );
+        assert_eq!(detect(&[], &[], None, sdkroot), path("/opt/sdkroot"));
+        assert_eq!(detect(&[], &[], None, None), None);
+    }
+
+    #[test]
+    fn detect_ignores_empty_and_root_sysroots() {
+        for root in ["", "/"] {
+            let root = Some(Path::new(root));
+            assert_eq!(detect(&[], &[], root, None), None);
+            assert_eq!(detect(&[], &[], None, root), None);
+            assert_eq!(
+                detect(&[], &[], root, Some(Path::new("/opt/sdk"))),
+                path("/opt/sdk")
+            );
+        }
+    }
+
+    #[test]
+    fn detect_ignores_include_paths_outside_an_sdk() {
+        let include_paths = [
+            PathBuf::from("/opt/sysroot/usr/include"),
+            PathBuf::from("/usr/include"),
+        ];
+        assert_eq!(detect(&[], &include_paths, None, None), None);
+    }
+
+    #[test]
+    fn from_cflags_accepts_every_flag_form() {
+        for (cflags, expected) in [
+            (&["--sysroot=/a"][..], "/a"),
+            (&["--sysroot", "/b"], "/b"),
+            (&["-isysroot", "/c"], "/c"),
+            (&["-isysroot/d"], "/d"),
+        ] {
+            assert_eq!(from_cflags(&strings(cflags)), path(expected));
+        }
+    }
+
+    #[test]
+    fn from_cflags_uses_the_last_flag() {
+        let cflags = strings(&[
+            "-isysroot",
+            "/a",
+            "-DX",
+            "--sysroot=/b",
+            "-isysroot/c",
+            "--sysroot",
+            "/d",
+            "-pthread",
+        ]);
+        assert_eq!(from_cflags(&cflags), path("/d"));
+        assert_eq!(
+            from_cflags(&strings(&["--sysroot=/a", "-isysroot"])),
+            path("/a")
+        );
+        assert_eq!(from_cflags(&strings(&["-I/usr/include", "-pthread"])), None);
+    }
+
+    #[test]
+    fn sdk_of_include_path_recognizes_apple_sdks() {
+        for sdk in [COMMAND_LINE_TOOLS_SDK, XCODE_SDK] {
+            let include_path = Path::new(sdk).join("usr/include");
+            assert_eq!(sdk_of_include_path(&include_path), Some(Path::new(sdk)));
+            assert_eq!(sdk_of_include_path(&Path::new(sdk).join("usr/lib")), None);
+        }
+        assert_eq!(sdk_of_include_path(Path::new("/usr/include")), None);
+        assert_eq!(
+            sdk_of_include_path(Path::new("/opt/aarch64-sysroot/usr/include")),
+            None
+        );
+    }
+
+    #[test]
+    fn is_default_include_dir_only_matches_the_sysroots_own_dirs() {
+        let sysroot = Path::new("/opt/sysroot");
+        assert!(is_default_include_dir(
+            Path::new("/opt/sysroot/usr/include"),
+            sysroot
+        ));
+        assert!(is_default_include_dir(
+            Path::new("/opt/sysroot/usr/local/include"),
+            sysroot
+        ));
+        assert!(!is_default_include_dir(
+            Path::new("/opt/sysroot/usr/include/boost"),
+            sysroot
+        ));
+        assert!(!is_default_include_dir(
+            Path::new("/opt/sysroot/opt/folly/include"),
+            sysroot
+        ));
+        assert!(!is_default_include_dir(Path::new("/usr/include"), sysroot));
+    }
+
+    #[test]
+    fn cflags_and_link_arg_depend_on_the_kind_of_sysroot() {
+        let sdk = Path::new(XCODE_SDK);
+        assert_eq!(cflags(sdk), strings(&["-isysroot", XCODE_SDK]));
+        assert_eq!(link_arg(sdk), None);
+        let sysroot = Path::new("/opt/sysroot");
+        assert_eq!(cflags(sysroot), strings(&["--sysroot=/opt/sysroot"]));
+        assert_eq!(link_arg(sysroot).as_deref(), Some("--sysroot=/opt/sysroot"));
+    }
+
+    #[test]
+    fn under_joins_absolute_paths_to_the_sysroot() {
+        let sysroot = Some(Path::new("/opt/sysroot"));
+        assert_eq!(
+            under(sysroot, Path::new("/usr/lib")),
+            PathBuf::from("/opt/sysroot/usr/lib")
+        );
+        assert_eq!(
+            under(sysroot, Path::new("usr/lib")),
+            PathBuf::from("/opt/sysroot/usr/lib")
+        );
+        assert_eq!(
+            under(None, Path::new("/usr/lib")),
+            PathBuf::from("/usr/lib")
+        );
+    }
+}
@@ -0,0 +1,270 @@
+// find-folly/src/version.rs
+//
+//! Folly version numbers.
+//!
+//! Folly is versioned by release date, with a trailing patch number: `2022.08.29.00`. Versions are
+//! compared component by component, numerically, so `2022.8.29` and `2022.08.29.00` are the same
+//! version.
+
+use crate::FollyError;
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::hash::{Hash, Hasher};
+use std::ops::Bound;
+use std::str::FromStr;
+
+/// A Folly version, such as `2022.08.29.00`.
+#[derive(Clone, Debug)]
+pub struct FollyVersion {
+    text: String,
+    components: Vec<u64>,
+}
+
+impl FollyVersion {
+    /// Returns the numeric components of the version, in order.
+    pub fn components(&self) -> &[u64] {
+        &self.components
+    }
+
+    // The components without trailing zeroes, which don't affect comparisons.
+    fn significant_components(&self) -> &[u64] {
+        let len = self
+            .components
+            .iter()
+            .rposition(|&component| component != 0)
+            .map_or(0, |last| last + 1);
+        &self.components[..len]
+    }
+}
+
+impl FromStr for FollyVersion {
+    type Err = FollyError;
+
+    /// Parses a version such as `2022.08.29.00` or `v2022.08.29.00`.
+    fn from_str(text: &str) -> Result<Self, FollyError> {
+        let text = text.trim();
+        let components = text
+            .strip_prefix('v')
+            .unwrap_or(text)
+            .split('.')
+            .map(|component| component.parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| FollyError::InvalidVersion(text.to_owned()))?;
+        Ok(FollyVersion {
+            text: text.to_owned(),
+            components,
+        })
+    }
+}
+
+impl Display for FollyVersion {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        f.write_str(&self.text)
+    }
+}
+
+impl PartialEq for FollyVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.significant_components() == other.significant_components()
+    }
+}
+
+impl Eq for FollyVersion {}
+
+impl PartialOrd for FollyVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FollyVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.significant_components()
+            .cmp(other.significant_components())
+    }
+}
+
+impl Hash for FollyVersion {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.significant_components().hash(state);
+    }
+}
+
+/// Checks `version` against the bounds given to the probe. An unknown version never satisfies a
+/// requirement.
+pub(crate) fn check(
+    version: Option<&FollyVersion>,
+    min: &Bound<String>,
+    max: &Bound<String>,
+) -> Result<(), FollyError> {
+    if matches!((min, max), (Bound::Unbounded, Bound::Unbounded)) {
+        return Ok(());
+    }
+    let min = parse_bound(min)?;
+    let max = parse_bound(max)?;
+    let satisfied = version.is_some_and(|version| {
+        let above_min = match min {
+            Bound::Included(ref min) => version >= min,
+            Bound::Excluded(ref min) => version > min,
+            Bound::Unbounded => true,
+        };
+        let below_max = match max {
+            Bound::Included(ref max) => version <= max,
+            Bound::Excluded(ref max) => version < max,
+            Bound::Unbounded => true,
+        };
+        above_min && below_max
+    });
+    if satisfied {
+        return Ok(());
+    }
+    Err(FollyError::Version {
+        found: version.cloned(),
+        required: describe_requirement(&min, &max),
+    })
+}
+
+fn parse_bound(bound: &Bound<String>) -> Result<Bound<FollyVersion>, FollyError> {
+    Ok(match *bound {
+        Bound::Included(ref version) => Bound::Included(version.parse()?),
+        Bound::Excluded(ref version) => Bound::Excluded(version.parse()?),
+        Bound::Unbounded => Bound::Unbounded,
+    })
+}
+
+fn describe_requirement(min: &Bound<FollyVersion>, max: &Bound<FollyVersion>) -> String {
+    if let (Bound::Included(min), Bound::Included(max)) = (min, max) {
+        if min == max {
+            return format!("= {}", min);
+        }
+    }
+    let mut parts = vec![];
+    match *min {
+        Bound::Included(ref min) => parts.push(format!(">= {}", min)),
+        Bound::Excluded(ref min) => parts.push(format!("> {}", min)),
+        Bound::Unbounded => {}
+    }
+    match *max {
+        Bound::Included(ref max) => parts.push(format!("<= {}", max)),
+        Bound::Excluded(ref max) => parts.push(format!("< {}", max)),
+        Bound::Unbounded => {}
+    }
+    parts.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(text: &str) -> FollyVersion {
+        text.parse().unwrap()
+    }
+
+    fn included(text: &str) -> Bound<String> {
+        Bound::Included(text.to_owned())
+    }
+
+    fn excluded(text: &str) -> Bound<String> {
+        Bound::Excluded(text.to_owned())
+    }
+
+    #[test]
+    fn parse_accepts_dates_and_a_leading_v() {
+        assert_eq!(version("2022.08.29.00").components(), [2022, 8, 29, 0]);
+        assert_eq!(version(" v2022.08.29.01\n").components(), [2022, 8, 29, 1]);
+        assert_eq!(version("v2022.08.29.01").to_string(), "v2022.08.29.01");
+    }
+
+    #[test]
+    fn parse_rejects_non_numeric_versions() {
+        for text in ["", "2023.01.02.00-dev", "2023..01", "latest"] {
+            assert!(matches!(
+                text.parse::<FollyVersion>(),
+                Err(FollyError::InvalidVersion(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn trailing_zeroes_are_insignificant() {
+        assert_eq!(version("2022.8.29"), version("2022.08.29.00"));
+        assert_eq!(version("2022.08.29.0.0"), version("2022.08.29"));
+        assert_eq!(
+            version("2022.8.29").cmp(&version("2022.08.29.00")),
+            Ordering::Equal
+        );
+        assert_ne!(version("2022.08.29.01"), version("2022.08.29"));
+    }
+
+    #[test]
+    fn versions_compare_numerically() {
+        assert!(version("2022.10.03.00") > version("2022.9.30.00"));
+        assert!(version("2022.08.29.01") > version("2022.08.29"));
+        assert!(version("2023.01.01") > version("2022.12.31.99"));
+        assert!(version("2022.08.29") < version("2022.08.29.00.01"));
+    }
+
+    #[test]
+    fn check_without_bounds_accepts_anything() {
+        assert!(check(None, &Bound::Unbounded, &Bound::Unbounded).is_ok());
+        assert!(check(
+            Some(&version("2022.08.29.00")),
+            &Bound::Unbounded,
+            &Bound::Unbounded
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn check_honors_inclusive_and_exclusive_bounds() {
+        let found = version("2022.08.29.00");
+        let found = Some(&found);
+        assert!(check(found, &included("2022.08.29"), &Bound::Unbounded).is_ok());
+        assert!(check(found, &excluded("2022.08.29"), &Bound::Unbounded).is_err());
+        assert!(check(found, &Bound::Unbounded, &included("2022.08.29.00")).is_ok());
+        assert!(check(found, &Bound::Unbounded, &excluded("2022.08.29.00")).is_err());
+        assert!(check(found, &included("2022.01.01"), &excluded("2023.01.01")).is_ok());
+        assert!(check(found, &included("2022.09.01"), &excluded("2023.01.01")).is_err());
+    }
+
+    #[test]
+    fn check_rejects_an_unknown_version_when_bounded() {
+        let error = check(None, &included("2022.08.29"), &Bound::Unbounded).unwrap_err();
+        assert!(matches!(
+            error,
+            FollyError::Version {
+                found: None,
+                ref required
+            } if required == ">= 2022.08.29"
+        ));
+    }
+
+    #[test]
+    fn check_describes_the_requirement() {
+        let found = version("2021.01.01");
+        let describe = |min, max| match check(Some(&found), &min, &max) {
+            Err(FollyError::Version { required, .. }) => required,
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(
+            describe(included("2022.08.29"), included("2022.08.29")),
+            "= 2022.08.29"
+        );
+        assert_eq!(
+            describe(excluded("2022.01.01"), excluded("2023.01.01")),
+            "> 2022.01.01, < 2023.01.01"
+        );
+        assert_eq!(
+            describe(Bound::Unbounded, included("2020.01.01")),
+            "<= 2020.01.01"
+        );
+    }
+
+    #[test]
+    fn check_rejects_an_invalid_bound() {
+        assert!(matches!(
+            check(None, &included("next"), &Bound::Unbounded),
+            Err(FollyError::InvalidVersion(_))
+        ));
+    }
+}